@@ -0,0 +1,61 @@
+//! managing the collection of decks
+
+use std::path::PathBuf;
+
+use chanki::{
+    collection::{Collection, CollectionError},
+    config::Config,
+};
+
+use crate::DeckCommand;
+
+/// exit with `e` as the error message
+fn fail(e: CollectionError) -> ! {
+    eprintln!("error: {e}");
+    std::process::exit(1);
+}
+
+/// open the collection in the directory from `config`, exiting on failure
+fn open(config: &Config) -> Collection {
+    config.open_decks().unwrap_or_else(|e| fail(e))
+}
+
+/// the path of the deck called `name`, or of the current deck if `name` is
+/// `None`. exits if there is no such deck
+pub(crate) fn deck_path(config: &Config, name: Option<&str>) -> PathBuf {
+    let decks = open(config);
+    let name = match name {
+        Some(name) => name.to_owned(),
+        None => decks.current().unwrap_or_else(|e| fail(e)),
+    };
+    if !decks.contains(&name) {
+        fail(CollectionError::NotFound(name));
+    }
+    decks.path(&name)
+}
+
+/// print the decks in the collection as a tree, marking the current one
+fn list(decks: &Collection) -> Result<(), CollectionError> {
+    let current = decks.current()?;
+    for name in decks.names()? {
+        let depth = name.matches('/').count();
+        let short = name.rsplit('/').next().unwrap_or(&name);
+        let mark = if name == current { "*" } else { " " };
+        println!("{mark} {}{short}", "  ".repeat(depth));
+    }
+    Ok(())
+}
+
+pub(crate) fn manage(config: &Config, action: DeckCommand) {
+    let decks = open(config);
+    let result = match action {
+        DeckCommand::List => list(&decks),
+        DeckCommand::Create { name } => decks.create(&name),
+        DeckCommand::Rename { from, to } => decks.rename(&from, &to),
+        DeckCommand::Delete { name } => decks.delete(&name),
+        DeckCommand::Switch { name } => decks.switch(&name),
+    };
+    if let Err(e) = result {
+        fail(e);
+    }
+}
@@ -1,41 +1,513 @@
-use chanki::{deck::Card, pgn::Pgn};
-use clap::Parser;
+use chanki::{
+    annotation::{self, Shape},
+    board::Color,
+    cache::RenderCache,
+    config::Config,
+    deck::{filter::Filter, simulate::Simulation, Card, CardId},
+    pgn::Pgn,
+    render::{Renderer, DEFAULT_SIZE},
+    theme::{BoardColors, PieceSet, Theme},
+};
+use clap::{Parser, Subcommand};
 use std::{
-    io::{stdin, Read},
+    error::Error,
+    io::{stdin, IsTerminal, Read},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
+mod decks;
+mod review;
+
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub(crate) struct Args {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
     /// Optional name of a PGN file to read. If absent, read the PGN from
     /// stdin
     pub(crate) pgn: Option<String>,
 
     /// Halfmove at which to generate the diagram. Black's second move is 4,
     /// for example
-    #[arg(short, long)]
-    pub(crate) move_number: usize,
+    #[arg(short, long, required = true)]
+    pub(crate) move_number: Option<usize>,
 
-    /// Name for the output diagram PNG file
+    /// Name for the output diagram PNG file. If the name ends in .svg, the
+    /// diagram is written as an SVG instead
     #[arg(short, long, default_value_t = String::from("out.png"))]
     pub(crate) output: String,
 
+    /// Side length of the output PNG in pixels
+    #[arg(short, long, default_value_t = DEFAULT_SIZE)]
+    pub(crate) size: u32,
+
+    /// Side to draw at the bottom of the diagram, white or black. Defaults to
+    /// the side to move
+    #[arg(long, value_parser = parse_color)]
+    pub(crate) orientation: Option<Color>,
+
+    /// Extra arrows to draw, in the format of Lichess's [%cal] comments,
+    /// like Ge2e4,Rd7d5. G, R, Y, and B select the color
+    #[arg(long)]
+    pub(crate) arrows: Option<String>,
+
+    /// Squares to circle, in the format of Lichess's [%csl] comments, like
+    /// Gd4,Re5
+    #[arg(long)]
+    pub(crate) circles: Option<String>,
+
+    /// Squares to highlight, in the same format as --circles
+    #[arg(long)]
+    pub(crate) highlights: Option<String>,
+
+    /// Board color scheme: brown, blue, green, or grey. Defaults to the one
+    /// in the config file
+    #[arg(long, value_parser = parse_board)]
+    pub(crate) board: Option<BoardColors>,
+
+    /// Piece set: builtin, cburnett, or a directory containing a set with
+    /// files named like wK.svg and bN.svg, such as one of Lichess's sets.
+    /// Defaults to the pieces in the config file
+    #[arg(long)]
+    pub(crate) pieces: Option<String>,
+
+    /// How to draw the PNG: native or latex. Defaults to the renderer in the
+    /// config file
+    #[arg(long, value_parser = parse_renderer)]
+    pub(crate) renderer: Option<Renderer>,
+
+    /// Don't label the ranks and files
+    #[arg(long)]
+    pub(crate) no_coordinates: bool,
+
+    /// Draw a frame around the board
+    #[arg(long)]
+    pub(crate) border: bool,
+
     /// Answer for the card,
-    #[arg(short, long)]
-    pub(crate) answer: String,
+    #[arg(short, long, required = true)]
+    pub(crate) answer: Option<String>,
+
+    /// Also write an animated GIF of the moves leading to the position to
+    /// this file
+    #[arg(long)]
+    pub(crate) gif: Option<String>,
+
+    /// Also print the position to the terminal
+    #[arg(long)]
+    pub(crate) show: bool,
+
+    /// Print boards in plain ASCII instead of with ANSI colors
+    #[arg(long, global = true)]
+    pub(crate) no_color: bool,
+
+    /// Name of the deck to use instead of the current one, like
+    /// white/sicilian for a sub-deck
+    #[arg(long, global = true)]
+    pub(crate) deck: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Show where the config file is read from and the settings in effect
+    Config,
+
+    /// Manage the collection of decks
+    Deck {
+        #[command(subcommand)]
+        action: DeckCommand,
+    },
+
+    /// Review the cards in the deck that are due, in the terminal
+    Review {
+        /// Only review cards whose tags match this expression, like
+        /// "ECO:C2* and not endgame"
+        #[arg(long)]
+        filter: Option<Filter>,
+    },
+
+    /// Choose the algorithm that schedules the deck's reviews
+    Scheduler {
+        /// sm2 or fsrs
+        #[arg(value_parser = ["sm2", "fsrs"])]
+        algorithm: String,
+
+        /// For FSRS, the probability of recalling a card to aim for, between
+        /// 0 and 1. Defaults to the deck's current retention if it already
+        /// uses FSRS, and 0.9 otherwise
+        #[arg(long)]
+        retention: Option<f64>,
+
+        /// For FSRS, the longest interval to schedule, in days. Defaults like
+        /// --retention
+        #[arg(long)]
+        maximum_interval: Option<f64>,
+    },
+
+    /// Show or set the deck's learning steps
+    Steps {
+        /// Delays before each repetition of a new card, like 1m,10m,1d
+        #[arg(long)]
+        learning: Option<String>,
+
+        /// Delays before each repetition of a forgotten card, like 10m
+        #[arg(long)]
+        relearning: Option<String>,
+    },
+
+    /// Fit the FSRS weights to the deck's review history
+    Optimize {
+        /// Switch the deck to FSRS with the fitted weights
+        #[arg(long)]
+        save: bool,
+    },
+
+    /// List the cards in the deck with their ids and when they are due
+    List {
+        /// Only list cards whose tags match this expression
+        #[arg(long)]
+        filter: Option<Filter>,
+    },
+
+    /// Render the diagram of each card in the deck to a directory, as
+    /// files named by card id like 12.png, using the renderer and theme from
+    /// the config file
+    Export {
+        /// Directory to write the diagrams to, which is created if it doesn't
+        /// exist
+        dir: PathBuf,
+
+        /// Only export cards whose tags match this expression
+        #[arg(long)]
+        filter: Option<Filter>,
+
+        /// Side length of each PNG in pixels
+        #[arg(short, long, default_value_t = DEFAULT_SIZE)]
+        size: u32,
+    },
+
+    /// Add tags to a card
+    Tag {
+        /// Id of the card, as shown by list
+        id: u64,
+
+        /// Tags to add, which can't contain spaces
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        /// Remove the tags instead
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Delete a card from the deck. Its reviews are kept in the deck's
+    /// history for optimize
+    Delete {
+        /// Id of the card, as shown by list
+        id: u64,
+    },
+
+    /// Leave a card out of reviews until it is unsuspended
+    Suspend {
+        /// Id of the card, as shown by list
+        id: u64,
+
+        /// Unsuspend the card instead
+        #[arg(long)]
+        undo: bool,
+    },
+
+    /// Leave a card out of reviews until tomorrow
+    Bury {
+        /// Id of the card, as shown by list
+        id: u64,
+
+        /// Unbury the card instead
+        #[arg(long)]
+        undo: bool,
+    },
+
+    /// List the cards that keep being forgotten, and show or set when cards
+    /// count as leeches
+    Leeches {
+        /// Number of lapses after which a card is tagged as a leech, or 0 to
+        /// never tag cards
+        #[arg(long)]
+        threshold: Option<usize>,
+
+        /// Whether to also suspend leeches, true or false
+        #[arg(long)]
+        suspend: Option<bool>,
+    },
+
+    /// Project how many cards will be due each day by simulating reviews
+    Forecast {
+        /// Number of days to simulate
+        #[arg(long, default_value_t = 30)]
+        days: usize,
+
+        /// Chance of remembering each card when it is reviewed, between 0
+        /// and 1
+        #[arg(long, default_value_t = 0.9)]
+        retention: f64,
+
+        /// Number of new cards to add to the deck over the simulation
+        #[arg(long, default_value_t = 0)]
+        new: usize,
+
+        /// Most of the new cards to start learning each day. Defaults to the
+        /// deck's limit
+        #[arg(long)]
+        new_per_day: Option<usize>,
+    },
+
+    /// List the backups kept of the deck, newest first
+    Backups,
+
+    /// Replace the deck with one of its backups, backing it up first
+    Restore {
+        /// Number of the backup, as shown by backups
+        number: usize,
+    },
+
+    /// Show or set how many cards the deck gives to review each day
+    Limits {
+        /// Most cards to see for the first time each day
+        #[arg(long)]
+        new_per_day: Option<usize>,
+
+        /// Most cards to review each day, not counting cards that are still
+        /// being learned
+        #[arg(long)]
+        reviews_per_day: Option<usize>,
+
+        /// Hour from 0 to 23, in UTC, at which each day starts
+        #[arg(long)]
+        day_start: Option<u32>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum DeckCommand {
+    /// List the decks in the collection, marking the current one
+    List,
+
+    /// Create an empty deck. Names like white/sicilian create sub-decks,
+    /// which are reviewed separately from their parent
+    Create { name: String },
+
+    /// Rename a deck and its sub-decks
+    Rename { from: String, to: String },
+
+    /// Delete a deck and its sub-decks
+    Delete { name: String },
+
+    /// Make a deck the current one, used when --deck isn't given
+    Switch { name: String },
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    match s.to_lowercase().as_str() {
+        "white" | "w" => Ok(Color::White),
+        "black" | "b" => Ok(Color::Black),
+        _ => Err(format!("expected white or black, got {s}")),
+    }
+}
+
+fn parse_board(s: &str) -> Result<BoardColors, String> {
+    BoardColors::from_name(s).ok_or_else(|| format!("unknown board {s}"))
+}
+
+fn parse_renderer(s: &str) -> Result<Renderer, String> {
+    Renderer::from_name(s).ok_or_else(|| format!("unknown renderer {s}"))
+}
+
+/// whether to draw boards with ANSI colors: only when writing to a terminal
+/// and not disabled by `--no-color` or the `NO_COLOR` convention
+fn use_ansi(no_color: bool) -> bool {
+    !no_color
+        && std::env::var_os("NO_COLOR").is_none()
+        && std::io::stdout().is_terminal()
 }
 
 fn main() {
     let args = Args::parse();
+    let ansi = use_ansi(args.no_color);
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    });
+    match args.command {
+        None => {
+            if let Err(e) = diagram(args, &config, ansi) {
+                eprintln!("error: {e}");
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Config) => show_config(&config),
+        Some(Command::Deck { action }) => decks::manage(&config, action),
+        Some(Command::Export { dir, filter, size }) => {
+            let path = decks::deck_path(&config, args.deck.as_deref());
+            review::export(&path, &dir, filter, &config, size)
+        }
+        Some(command) => {
+            let path = decks::deck_path(&config, args.deck.as_deref());
+            run(command, &path, ansi)
+        }
+    }
+}
+
+/// print the path of the config file and `config` as it would be written
+/// there
+fn show_config(config: &Config) {
+    println!("# {}", Config::default_path().display());
+    match config.to_toml() {
+        Ok(s) => print!("{s}"),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// run one of the commands that operate on the deck at `path`
+fn run(command: Command, path: &Path, ansi: bool) {
+    match command {
+        Command::Review { filter } => review::review(path, filter, ansi),
+        Command::Scheduler {
+            algorithm,
+            retention,
+            maximum_interval,
+        } => {
+            review::set_scheduler(path, &algorithm, retention, maximum_interval)
+        }
+        Command::Optimize { save } => review::optimize(path, save),
+        Command::Steps {
+            learning,
+            relearning,
+        } => review::set_steps(path, learning, relearning),
+        Command::List { filter } => review::list(path, filter),
+        Command::Tag { id, tags, remove } => {
+            review::tag(path, CardId(id), &tags, remove)
+        }
+        Command::Delete { id } => review::delete(path, CardId(id)),
+        Command::Suspend { id, undo } => {
+            review::suspend(path, CardId(id), !undo)
+        }
+        Command::Bury { id, undo } => review::bury(path, CardId(id), !undo),
+        Command::Leeches { threshold, suspend } => {
+            review::leeches(path, threshold, suspend)
+        }
+        Command::Forecast {
+            days,
+            retention,
+            new,
+            new_per_day,
+        } => review::forecast(
+            path,
+            Simulation {
+                days,
+                retention,
+                new_cards: new,
+                ..Simulation::default()
+            },
+            new_per_day,
+        ),
+        Command::Backups => review::list_backups(path),
+        Command::Restore { number } => review::restore(path, number),
+        Command::Limits {
+            new_per_day,
+            reviews_per_day,
+            day_start,
+        } => review::limits(path, new_per_day, reviews_per_day, day_start),
+        Command::Config | Command::Deck { .. } | Command::Export { .. } => {
+            unreachable!()
+        }
+    }
+}
+
+/// generate a diagram from the PGN and options in `args`
+fn diagram(
+    args: Args,
+    config: &Config,
+    ansi: bool,
+) -> Result<(), Box<dyn Error>> {
     let pgn = if let Some(pgn) = args.pgn {
-        Pgn::load(pgn).unwrap()
+        Pgn::load(pgn)?
     } else {
         let mut s = String::new();
-        stdin().read_to_string(&mut s).unwrap();
-        Pgn::from_str(&s).unwrap()
+        stdin().read_to_string(&mut s)?;
+        Pgn::from_str(&s)?
     };
 
-    Card::new(&pgn, args.move_number, args.output, args.answer);
+    let theme = Theme {
+        board: args.board.unwrap_or_else(|| config.theme.board.clone()),
+        pieces: match args.pieces {
+            Some(name) => match PieceSet::from_name(&name) {
+                Some(set) => set,
+                None => PieceSet::directory(name)?,
+            },
+            None => config.theme.pieces.clone(),
+        },
+        coordinates: config.theme.coordinates && !args.no_coordinates,
+        border: config.theme.border || args.border,
+    };
+
+    // both are required by clap unless a subcommand is given
+    let (Some(move_number), Some(answer)) = (args.move_number, args.answer)
+    else {
+        unreachable!()
+    };
+    let mut card = Card::new(&pgn, move_number, answer)?;
+    let pos = card.position.as_mut().unwrap();
+    pos.orientation = args.orientation;
+    if let Some(arrows) = args.arrows {
+        pos.shapes.extend(
+            annotation::parse_arrows(&arrows).ok_or("invalid --arrows")?,
+        );
+    }
+    if let Some(circles) = args.circles {
+        pos.shapes.extend(
+            annotation::parse_squares(&circles, |square, brush| {
+                Shape::Circle { square, brush }
+            })
+            .ok_or("invalid --circles")?,
+        );
+    }
+    if let Some(highlights) = args.highlights {
+        pos.shapes.extend(
+            annotation::parse_squares(&highlights, |square, brush| {
+                Shape::Highlight { square, brush }
+            })
+            .ok_or("invalid --highlights")?,
+        );
+    }
+    if args.output.ends_with(".svg") {
+        let board = pos.board()?;
+        let svg = board.to_svg(&pos.all_shapes(), pos.flip(&board), &theme);
+        std::fs::write(&args.output, svg)?;
+    } else {
+        let png = card.render_cached(
+            &RenderCache::default(),
+            args.renderer.unwrap_or(config.renderer),
+            &theme,
+            args.size,
+        )?;
+        std::fs::write(&args.output, png)?;
+    }
+    if let Some(gif) = &args.gif {
+        let pos = card.position.as_ref().unwrap();
+        std::fs::write(gif, pos.line_gif(&theme, args.size)?)?;
+    }
+    if args.show {
+        print!("{}", card.position.unwrap().to_term(ansi)?);
+    }
+    Ok(())
 }
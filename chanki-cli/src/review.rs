@@ -0,0 +1,463 @@
+//! reviewing cards in the terminal
+
+use std::{
+    io::{stdin, stdout, Write},
+    path::Path,
+    time::Instant,
+};
+
+use chanki::{
+    backup,
+    clock::{Clock, SystemClock},
+    config::Config,
+    deck::{
+        self,
+        filter::Filter,
+        simulate::{self, Simulation},
+        Card, CardId, Deck, Quality,
+    },
+    optimize, render,
+    scheduler::{Fsrs, SchedulerKind},
+};
+
+/// print the front of `card`: its position, or the path to its image for
+/// cards from before positions were stored
+fn print_card(card: &Card, ansi: bool) {
+    match (&card.position, &card.image) {
+        (Some(pos), _) => match pos.to_term(ansi) {
+            Ok(board) => print!("{board}"),
+            Err(e) => println!("failed to draw position {}: {e}", pos.fen),
+        },
+        (None, Some(image)) => println!("see {image}"),
+        (None, None) => println!("card has no position"),
+    }
+}
+
+/// print `msg` and read a line from stdin. returns `None` at EOF
+fn prompt(msg: &str) -> Option<String> {
+    print!("{msg}");
+    stdout().flush().unwrap();
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_owned()),
+    }
+}
+
+/// load the deck at `path`, exiting with an error if it can't be read
+fn load_deck(path: &Path) -> Deck {
+    Deck::load(path).unwrap_or_else(|e| {
+        eprintln!("error loading deck: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// switch the deck to the scheduler named `algorithm`, setting the FSRS
+/// `retention` and `maximum_interval` that are given. a deck that already
+/// uses FSRS keeps its weights and other settings. cards keep their existing
+/// state, so FSRS starts tracking their memory from their next review
+pub(crate) fn set_scheduler(
+    path: &Path,
+    algorithm: &str,
+    retention: Option<f64>,
+    maximum_interval: Option<f64>,
+) {
+    if retention.is_some_and(|r| r <= 0.0 || r >= 1.0) {
+        eprintln!("retention must be between 0 and 1");
+        std::process::exit(1);
+    }
+    if maximum_interval.is_some_and(|days| days < 1.0) {
+        eprintln!("maximum interval must be at least 1 day");
+        std::process::exit(1);
+    }
+    let mut deck = load_deck(path);
+    deck.scheduler = match algorithm {
+        "fsrs" => {
+            // keep the weights of a deck that already uses FSRS, which may
+            // have been fitted by optimize
+            let mut fsrs = match deck.scheduler {
+                SchedulerKind::Fsrs(fsrs) => fsrs,
+                SchedulerKind::Sm2 => Fsrs::default(),
+            };
+            if let Some(retention) = retention {
+                fsrs.desired_retention = retention;
+            }
+            if let Some(days) = maximum_interval {
+                fsrs.maximum_interval = days;
+            }
+            SchedulerKind::Fsrs(fsrs)
+        }
+        _ => SchedulerKind::Sm2,
+    };
+    deck.dump(path).unwrap();
+}
+
+/// replace the deck's learning and relearning steps with any that are given,
+/// then print them
+pub(crate) fn set_steps(
+    path: &Path,
+    learning: Option<String>,
+    relearning: Option<String>,
+) {
+    let parse = |s: &str| {
+        deck::parse_steps(s).unwrap_or_else(|| {
+            eprintln!("invalid steps {s}, expected a list like 1m,10m,1d");
+            std::process::exit(1);
+        })
+    };
+    let mut deck = load_deck(path);
+    if let Some(steps) = &learning {
+        deck.learning_steps = parse(steps);
+    }
+    if let Some(steps) = &relearning {
+        deck.relearning_steps = parse(steps);
+    }
+    if learning.is_some() || relearning.is_some() {
+        deck.dump(path).unwrap();
+    }
+    println!("learning: {}", deck::format_steps(&deck.learning_steps));
+    println!("relearning: {}", deck::format_steps(&deck.relearning_steps));
+}
+
+/// fit the FSRS weights to the deck's reviews and report how well they
+/// predict them, storing them in the deck if `save` is true
+pub(crate) fn optimize(path: &Path, save: bool) {
+    let mut deck = load_deck(path);
+    let start = match &deck.scheduler {
+        SchedulerKind::Fsrs(fsrs) => fsrs.clone(),
+        SchedulerKind::Sm2 => Fsrs::default(),
+    };
+    let report = match optimize::optimize(&start, &deck) {
+        Ok(report) => report,
+        Err(_) => {
+            eprintln!("no cards have been reviewed more than once yet");
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "fit to {} reviews, {:.1}% recalled",
+        report.reviews,
+        100.0 * report.actual_retention
+    );
+    println!("{:8}{:>10}{:>21}", "", "log loss", "predicted retention");
+    for (name, fit) in [("before", report.before), ("after", report.after)] {
+        println!(
+            "{name:8}{:>10.4}{:>20.1}%",
+            fit.log_loss,
+            100.0 * fit.predicted_retention
+        );
+    }
+    if save {
+        deck.scheduler = SchedulerKind::Fsrs(Fsrs {
+            weights: report.weights,
+            ..start
+        });
+        deck.dump(path).unwrap();
+        println!("saved the weights to the deck");
+    } else {
+        println!("run with --save to use these weights");
+    }
+}
+
+/// print each card in the deck with its id, due date, and state
+pub(crate) fn list(path: &Path, filter: Option<Filter>) {
+    let deck = load_deck(path);
+    println!(
+        "{:>5}  {:16}  {:10}  {:10}  tags",
+        "id", "due", "state", "answer"
+    );
+    let matching = deck
+        .cards
+        .iter()
+        .filter(|c| filter.as_ref().is_none_or(|f| f.matches(c)));
+    for card in matching {
+        let state = if card.suspended {
+            "suspended"
+        } else if !deck.is_active(card) {
+            "buried"
+        } else if deck.is_due(card) {
+            "due"
+        } else {
+            ""
+        };
+        println!(
+            "{:>5}  {:16}  {state:10}  {:10}  {}",
+            card.id,
+            card.next_due.format("%Y-%m-%d %H:%M").to_string(),
+            card.answer,
+            card.tags.join(" ")
+        );
+    }
+}
+
+/// write the diagrams of the cards in the deck at `path` that match `filter`
+/// to `dir`, rendering them in parallel. cards that fail are reported and
+/// the rest are still written
+pub(crate) fn export(
+    path: &Path,
+    dir: &Path,
+    filter: Option<Filter>,
+    config: &Config,
+    size: u32,
+) {
+    let deck = load_deck(path);
+    let cards: Vec<Card> = deck
+        .cards
+        .into_iter()
+        .filter(|c| filter.as_ref().is_none_or(|f| f.matches(c)))
+        .collect();
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!("error creating {}: {e}", dir.display());
+        std::process::exit(1);
+    }
+    let pngs = render::render_all(&cards, config.renderer, &config.theme, size);
+    let mut failed = 0;
+    for (card, png) in cards.iter().zip(pngs) {
+        let file = dir.join(format!("{}.png", card.id));
+        let result = png.map_err(|e| e.to_string()).and_then(|png| {
+            std::fs::write(&file, png).map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            eprintln!("card {}: {e}", card.id);
+            failed += 1;
+        }
+    }
+    println!(
+        "wrote {} diagrams to {}",
+        cards.len() - failed,
+        dir.display()
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// add `tags` to the card with `id`, or remove them if `remove` is true
+pub(crate) fn tag(path: &Path, id: CardId, tags: &[String], remove: bool) {
+    if let Some(tag) = tags.iter().find(|t| t.contains(char::is_whitespace)) {
+        eprintln!("tag {tag:?} contains whitespace");
+        std::process::exit(1);
+    }
+    let mut deck = load_deck(path);
+    let card = deck.get_mut(id);
+    let found = card.is_some();
+    if let Some(card) = card {
+        if remove {
+            card.tags.retain(|t| !tags.contains(t));
+        } else {
+            tags.iter().for_each(|t| card.add_tag(t));
+        }
+    }
+    save_if_found(path, &deck, id, found);
+}
+
+/// print the leeches in the deck, after updating the leech settings with
+/// those given
+pub(crate) fn leeches(
+    path: &Path,
+    threshold: Option<usize>,
+    suspend: Option<bool>,
+) {
+    let mut deck = load_deck(path);
+    if let Some(threshold) = threshold {
+        deck.leech_threshold = threshold;
+    }
+    if let Some(suspend) = suspend {
+        deck.leech_suspend = suspend;
+    }
+    if threshold.is_some() || suspend.is_some() {
+        deck.dump(path).unwrap();
+    }
+    println!(
+        "threshold: {} lapses, suspend: {}",
+        deck.leech_threshold, deck.leech_suspend
+    );
+    let leeches = deck.leeches();
+    if leeches.is_empty() {
+        println!("no leeches");
+        return;
+    }
+    println!("{:>5}  {:>6}  {:9}  answer", "id", "lapses", "state");
+    for card in leeches {
+        let state = if card.suspended { "suspended" } else { "" };
+        println!(
+            "{:>5}  {:>6}  {state:9}  {}",
+            card.id, card.lapses, card.answer
+        );
+    }
+}
+
+/// print the deck's daily limits and how much of them is used up today,
+/// after updating them with those given
+pub(crate) fn limits(
+    path: &Path,
+    new_per_day: Option<usize>,
+    reviews_per_day: Option<usize>,
+    day_start: Option<u32>,
+) {
+    if day_start.is_some_and(|h| h >= 24) {
+        eprintln!("the day must start at an hour from 0 to 23");
+        std::process::exit(1);
+    }
+    let mut deck = load_deck(path);
+    let limits = &mut deck.limits;
+    if let Some(n) = new_per_day {
+        limits.new_per_day = n;
+    }
+    if let Some(n) = reviews_per_day {
+        limits.reviews_per_day = n;
+    }
+    if let Some(hour) = day_start {
+        limits.day_start = hour;
+    }
+    if new_per_day.is_some() || reviews_per_day.is_some() || day_start.is_some()
+    {
+        deck.dump(path).unwrap();
+    }
+    let (new, reviews) = deck.studied_today();
+    let limits = &deck.limits;
+    println!("new cards: {new} of {} today", limits.new_per_day);
+    println!("reviews: {reviews} of {} today", limits.reviews_per_day);
+    println!("days start at {:02}:00 UTC", limits.day_start);
+}
+
+/// the backups of the deck at `path`, newest first, exiting if they can't be
+/// read
+fn backups(path: &Path) -> Vec<backup::Backup> {
+    backup::list(path).unwrap_or_else(|e| {
+        eprintln!("error reading backups: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// print the backups of the deck at `path`, numbered for [restore]
+pub(crate) fn list_backups(path: &Path) {
+    let backups = backups(path);
+    if backups.is_empty() {
+        println!("no backups");
+    }
+    for (i, backup) in backups.iter().enumerate() {
+        println!("{i:>3}  {}", backup.time.format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+}
+
+/// replace the deck at `path` with its backup numbered `number` by
+/// [list_backups]
+pub(crate) fn restore(path: &Path, number: usize) {
+    let backups = backups(path);
+    let Some(backup) = backups.get(number) else {
+        eprintln!("no backup {number}, see the backups command");
+        std::process::exit(1);
+    };
+    if let Err(e) = backup::restore(path, backup, SystemClock.now()) {
+        eprintln!("error restoring backup: {e}");
+        std::process::exit(1);
+    }
+    println!(
+        "restored the backup from {}, after backing up the deck",
+        backup.time.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+}
+
+/// save `deck` to `path`, or exit if there is no card with `id`. `found` is
+/// the result of the operation on the card
+fn save_if_found(path: &Path, deck: &Deck, id: CardId, found: bool) {
+    if !found {
+        eprintln!("no card with id {id}");
+        std::process::exit(1);
+    }
+    deck.dump(path).unwrap();
+}
+
+pub(crate) fn delete(path: &Path, id: CardId) {
+    let mut deck = load_deck(path);
+    let found = deck.remove(id).is_some();
+    save_if_found(path, &deck, id, found);
+}
+
+pub(crate) fn suspend(path: &Path, id: CardId, suspended: bool) {
+    let mut deck = load_deck(path);
+    let found = deck.suspend(id, suspended);
+    save_if_found(path, &deck, id, found);
+}
+
+pub(crate) fn bury(path: &Path, id: CardId, buried: bool) {
+    let mut deck = load_deck(path);
+    let found = deck.bury(id, buried);
+    save_if_found(path, &deck, id, found);
+}
+
+/// print the daily workload projected by [simulate::forecast] for the deck,
+/// starting `new_per_day` new cards a day or as many as the deck's limit
+pub(crate) fn forecast(
+    path: &Path,
+    sim: Simulation,
+    new_per_day: Option<usize>,
+) {
+    if sim.retention < 0.0 || sim.retention > 1.0 {
+        eprintln!("retention must be between 0 and 1");
+        std::process::exit(1);
+    }
+    let deck = load_deck(path);
+    let sim = Simulation {
+        new_per_day: new_per_day.unwrap_or(deck.limits.new_per_day),
+        ..sim
+    };
+    let days = simulate::forecast(&deck, &sim);
+    println!("{:>4}{:>7}{:>5}{:>9}", "day", "cards", "new", "reviews");
+    for (d, day) in days.iter().enumerate() {
+        println!("{d:>4}{:>7}{:>5}{:>9}", day.cards, day.new, day.reviews);
+    }
+    let total: usize = days.iter().map(|d| d.cards).sum();
+    let peak = days.iter().map(|d| d.cards).max().unwrap_or(0);
+    println!(
+        "{:.1} cards a day on average, at most {peak}",
+        total as f64 / days.len().max(1) as f64
+    );
+}
+
+/// review the due cards in the deck at `path` that match `filter`, up to
+/// the deck's daily limits, saving after each answer
+pub(crate) fn review(path: &Path, filter: Option<Filter>, ansi: bool) {
+    let mut deck = load_deck(path);
+    let mut reviewed = 0;
+    let queue = |deck: &Deck| match &filter {
+        Some(filter) => deck.queue_matching(filter),
+        None => deck.queue(),
+    };
+    // the queue changes as cards move through their learning steps, so
+    // rebuild it after every answer
+    while let Some(&id) = queue(&deck).first() {
+        reviewed += 1;
+        println!("card {reviewed}, {} left", queue(&deck).len() - 1);
+        let card = deck.get(id).unwrap();
+        print_card(card, ansi);
+        let shown = Instant::now();
+        if prompt("press enter to show the answer").is_none() {
+            return;
+        }
+        println!("answer: {}", card.answer);
+        let q = loop {
+            let Some(line) = prompt("quality (0-5, q to quit): ") else {
+                return;
+            };
+            if line == "q" {
+                return;
+            }
+            match line
+                .parse()
+                .map_err(|e| format!("{e}"))
+                .and_then(|q: u8| Quality::try_from(q))
+            {
+                Ok(q) => break q,
+                Err(e) => println!("{e}"),
+            }
+        };
+        deck.answer(id, q, shown.elapsed());
+        deck.dump(path).unwrap();
+        println!();
+    }
+    if reviewed == 0 {
+        println!("no cards due");
+    }
+}
@@ -1,19 +1,41 @@
-use std::{error::Error, io::Read, path::Path, str::FromStr};
+use std::{
+    error::Error,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use chanki::{
-    deck::{Card, Deck, Quality},
+    animation::{FINAL_DELAY, FRAME_DELAY},
+    board::Color,
+    cache::RenderCache,
+    collection::{Collection, DEFAULT_DECK},
+    config::Config,
+    deck::{
+        filter::FilterError,
+        simulate::{self, Day, Simulation},
+        Card, CardId, Deck, Quality,
+    },
     pgn::Pgn,
-    DECK_PATH,
+    render::{Renderer, DEFAULT_SIZE},
+    scheduler::{Fsrs, SchedulerKind},
+    theme::{BoardColors, PieceSet},
 };
 use eframe::CreationContext;
 use egui::{
     Button, Pos2, Rect, TextEdit, TextureHandle, TextureOptions, Ui, Visuals,
 };
-use rand::seq::SliceRandom;
+
+/// the frames of [chanki::deck::Position::line] for the card with `id`
+struct LineAnimation {
+    frames: Vec<TextureHandle>,
+    id: CardId,
+}
 
 struct CardImage {
-    texture: TextureHandle,
-    index: usize,
+    /// `None` if the card failed to render, in which case [App::error] says
+    /// why
+    texture: Option<TextureHandle>,
+    id: CardId,
 }
 
 pub struct App {
@@ -21,40 +43,101 @@ pub struct App {
     pgn: String,
     half_move: String,
     answer: String,
+    orientation: Option<Color>,
     deck: Deck,
     cur_card: Option<CardImage>,
-    some_review: bool,
+    line: Option<LineAnimation>,
+    /// the tag expression limiting which cards are reviewed, or empty for
+    /// all of them
+    filter: String,
+    /// input for the tags of the card in [Self::edit_view]
+    tags: String,
+    /// the card that `tags` was filled in from
+    tags_card: Option<CardId>,
+    /// when the card under review was first shown, for timing the answer
+    shown_at: Option<Instant>,
+    /// the settings from the config file, which [Self::settings_view] writes
+    /// back to it
+    config: Config,
+    /// input for the piece set directory in [Self::settings_view]
+    pieces: String,
+    /// the last error from loading `pieces`
+    pieces_error: Option<String>,
+    /// the last error from building or rendering a card, shown until the next
+    /// attempt
+    error: Option<String>,
+    cache: RenderCache,
+    /// the settings for [Self::forecast_view]
+    simulation: Simulation,
+    /// the result of the last forecast, with the settings it was run with
+    forecast: Option<(Simulation, Vec<Day>)>,
+    decks: Collection,
+    /// the name of `deck` in `decks`
+    deck_name: String,
+    /// input for deck names in [Self::decks_view]
+    deck_input: String,
 }
 
 impl Default for App {
     fn default() -> Self {
-        let deck = Deck::load(DECK_PATH).unwrap_or_else(|e| {
+        let config = Config::load().unwrap_or_else(|e| {
+            eprintln!("error loading the config: {e}");
+            Config::default()
+        });
+        let decks = config.open_decks().unwrap_or_else(|e| {
+            eprintln!("error opening decks: {e}");
+            std::process::exit(1);
+        });
+        let pieces = match &config.theme.pieces {
+            PieceSet::Directory(dir) => dir.display().to_string(),
+            PieceSet::Builtin | PieceSet::Cburnett => String::new(),
+        };
+        let deck_name = decks.current().unwrap_or_else(|e| {
+            eprintln!("error finding the current deck: {e}");
+            DEFAULT_DECK.to_owned()
+        });
+        let deck = decks.load(&deck_name).unwrap_or_else(|e| {
             eprintln!("error loading deck: {e}");
             Deck::default()
         });
-        let some_review = deck.cards.iter().any(|c| c.is_due());
+        let simulation = Simulation {
+            new_per_day: deck.limits.new_per_day,
+            ..Simulation::default()
+        };
         Self {
             view: Self::main_view,
             pgn: String::new(),
             half_move: String::new(),
             answer: String::new(),
+            orientation: None,
             deck,
             cur_card: None,
-            some_review,
+            line: None,
+            filter: String::new(),
+            tags: String::new(),
+            tags_card: None,
+            shown_at: None,
+            config,
+            pieces,
+            pieces_error: None,
+            error: None,
+            cache: RenderCache::default(),
+            simulation,
+            forecast: None,
+            decks,
+            deck_name,
+            deck_input: String::new(),
         }
     }
 }
 
-/// load a PNG image from `path` and convert it to an `egui::ColorImage`.
-/// adapted from egui_extras
+/// load a PNG image from `image_bytes` and convert it to an
+/// `egui::ColorImage`. adapted from egui_extras
 pub fn load_image(
-    path: impl AsRef<Path>,
+    image_bytes: &[u8],
 ) -> Result<egui::ColorImage, Box<dyn Error>> {
-    let mut f = std::fs::File::open(path)?;
-    let mut image_bytes = Vec::new();
-    f.read_to_end(&mut image_bytes)?;
     let image =
-        image::load_from_memory(&image_bytes).map_err(|err| err.to_string())?;
+        image::load_from_memory(image_bytes).map_err(|err| err.to_string())?;
     let size = [image.width() as _, image.height() as _];
     let image_buffer = image.to_rgba8();
     let pixels = image_buffer.as_flat_samples();
@@ -71,7 +154,111 @@ impl App {
         Default::default()
     }
 
+    /// write the deck to its file in the collection
+    fn save(&mut self) {
+        if let Err(e) = self.decks.save(&self.deck_name, &self.deck) {
+            self.error = Some(format!("failed to save the deck: {e}"));
+        }
+    }
+
+    /// switch to the deck called `name`, creating it if it doesn't exist
+    fn open_deck(&mut self, name: String) {
+        if !self.decks.contains(&name) {
+            if let Err(e) = self.decks.create(&name) {
+                self.error = Some(e.to_string());
+                return;
+            }
+        }
+        match self.decks.load(&name) {
+            Ok(deck) => {
+                let _ = self.decks.switch(&name);
+                self.simulation.new_per_day = deck.limits.new_per_day;
+                self.deck = deck;
+                self.deck_name = name;
+                self.cur_card = None;
+                self.line = None;
+                self.tags_card = None;
+                self.forecast = None;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn decks_view(&mut self, ui: &mut egui::Ui) {
+        let names = self.decks.names().unwrap_or_default();
+        for name in names {
+            let depth = name.matches('/').count();
+            let short = name.rsplit('/').next().unwrap_or(&name).to_owned();
+            ui.horizontal(|ui| {
+                ui.add_space(16.0 * depth as f32);
+                let current = name == self.deck_name;
+                if ui.selectable_label(current, short).clicked() && !current {
+                    self.open_deck(name);
+                }
+            });
+        }
+
+        TextEdit::singleline(&mut self.deck_input)
+            .hint_text("Deck name, like white/sicilian")
+            .show(ui);
+        let input = self.deck_input.trim().to_owned();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!input.is_empty(), Button::new("Create"))
+                .clicked()
+            {
+                match self.decks.create(&input) {
+                    Ok(()) => {
+                        self.open_deck(input.clone());
+                        self.deck_input.clear();
+                    }
+                    Err(e) => self.error = Some(e.to_string()),
+                }
+            }
+            if ui
+                .add_enabled(!input.is_empty(), Button::new("Rename"))
+                .clicked()
+            {
+                match self.decks.rename(&self.deck_name, &input) {
+                    Ok(()) => {
+                        self.deck_name = input.clone();
+                        self.deck_input.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e.to_string()),
+                }
+            }
+            if ui.add(Button::new("Delete")).clicked() {
+                match self.decks.delete(&self.deck_name) {
+                    Ok(()) => {
+                        let next = self
+                            .decks
+                            .current()
+                            .unwrap_or_else(|_| DEFAULT_DECK.to_owned());
+                        self.open_deck(next);
+                    }
+                    Err(e) => self.error = Some(e.to_string()),
+                }
+            }
+        });
+        ui.label(format!(
+            "Rename and Delete apply to {} and its sub-decks",
+            self.deck_name
+        ));
+        self.show_error(ui);
+
+        if ui.add(Button::new("Done")).clicked() {
+            self.error = None;
+            self.view = Self::main_view;
+        }
+    }
+
     fn main_view(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Deck: {}", self.deck_name));
+        if ui.add(Button::new("Decks")).clicked() {
+            self.view = Self::decks_view;
+        }
         if ui.add(Button::new("Add card")).clicked() {
             self.view = Self::add_view;
         }
@@ -79,29 +266,273 @@ impl App {
             self.view = Self::edit_view;
         }
 
+        TextEdit::singleline(&mut self.filter)
+            .hint_text("Filter by tags, like ECO:C2* and not endgame")
+            .show(ui);
+        let due = match self.review_queue() {
+            Ok(queue) => queue.len(),
+            Err(e) => {
+                ui.colored_label(egui::Color32::RED, e.to_string());
+                0
+            }
+        };
         if ui
-            .add_enabled(self.some_review, Button::new("Review"))
+            .add_enabled(due > 0, Button::new(format!("Review ({due} due)")))
             .clicked()
         {
             self.view = Self::review_prompt;
         }
+        if ui.add(Button::new("Leeches")).clicked() {
+            self.view = Self::leech_view;
+        }
+        if ui.add(Button::new("Forecast")).clicked() {
+            self.view = Self::forecast_view;
+        }
+        if ui.add(Button::new("Settings")).clicked() {
+            self.view = Self::settings_view;
+        }
+    }
+
+    /// the cards that keep being forgotten, to rework in the edit view
+    fn leech_view(&mut self, ui: &mut egui::Ui) {
+        let old = (self.deck.leech_threshold, self.deck.leech_suspend);
+        ui.add(
+            egui::Slider::new(&mut self.deck.leech_threshold, 0..=20)
+                .text("Lapses before a card is a leech"),
+        );
+        ui.checkbox(&mut self.deck.leech_suspend, "Suspend leeches");
+        if (self.deck.leech_threshold, self.deck.leech_suspend) != old {
+            self.save();
+        }
+
+        let leeches: Vec<_> = self
+            .deck
+            .leeches()
+            .iter()
+            .map(|c| (c.id, c.lapses, c.suspended, c.answer.clone()))
+            .collect();
+        if leeches.is_empty() {
+            ui.label("No leeches");
+        }
+        egui::Grid::new("leeches").striped(true).show(ui, |ui| {
+            for (id, lapses, suspended, answer) in leeches {
+                ui.label(format!("{lapses} lapses"));
+                ui.label(if suspended { "Suspended" } else { "" });
+                ui.label(answer);
+                if ui.add(Button::new("Edit")).clicked() {
+                    self.show_card(ui, id);
+                    self.view = Self::edit_view;
+                }
+                ui.end_row();
+            }
+        });
+
+        if ui.add(Button::new("Done")).clicked() {
+            self.view = Self::main_view;
+        }
+    }
+
+    fn forecast_view(&mut self, ui: &mut egui::Ui) {
+        let sim = &mut self.simulation;
+        let sliders = [
+            ui.add(egui::Slider::new(&mut sim.days, 1..=365).text("Days")),
+            ui.add(
+                egui::Slider::new(&mut sim.retention, 0.5..=1.0)
+                    .text("Retention"),
+            ),
+            ui.add(
+                egui::Slider::new(&mut sim.new_cards, 0..=1000)
+                    .text("New cards"),
+            ),
+            ui.add(
+                egui::Slider::new(&mut sim.new_per_day, 1..=100)
+                    .text("New cards per day"),
+            ),
+        ];
+        // a long forecast takes a moment, so wait until a slider is let go
+        // rather than running it for every step of a drag
+        let dragging = sliders.iter().any(|r| r.dragged());
+        let stale = self
+            .forecast
+            .as_ref()
+            .is_none_or(|(sim, _)| *sim != self.simulation);
+        if stale && !dragging {
+            let days = simulate::forecast(&self.deck, &self.simulation);
+            self.forecast = Some((self.simulation, days));
+        } else if stale {
+            ui.label("Release the slider to update the forecast");
+        }
+        let days = match &self.forecast {
+            Some((_, days)) => days.as_slice(),
+            None => &[],
+        };
+
+        let peak = days.iter().map(|d| d.cards).max().unwrap_or(0);
+        ui.label(format!("At most {peak} cards in a day"));
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .show(ui, |ui| {
+                egui::Grid::new("forecast").striped(true).show(ui, |ui| {
+                    for heading in ["Day", "Cards", "New", "Reviews"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for (d, day) in days.iter().enumerate() {
+                        ui.label(d.to_string());
+                        ui.label(day.cards.to_string());
+                        ui.label(day.new.to_string());
+                        ui.label(day.reviews.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if ui.add(Button::new("Done")).clicked() {
+            // the deck may have changed by the next visit
+            self.forecast = None;
+            self.view = Self::main_view;
+        }
+    }
+
+    fn settings_view(&mut self, ui: &mut egui::Ui) {
+        let old = self.config.clone();
+        egui::ComboBox::from_label("Board")
+            .selected_text(format!("{:?}", self.config.theme.board))
+            .show_ui(ui, |ui| {
+                for board in BoardColors::NAMED {
+                    let text = format!("{board:?}");
+                    ui.selectable_value(
+                        &mut self.config.theme.board,
+                        board,
+                        text,
+                    );
+                }
+            });
+        ui.checkbox(&mut self.config.theme.coordinates, "Coordinates");
+        ui.checkbox(&mut self.config.theme.border, "Border");
+        ui.horizontal(|ui| {
+            ui.label("Renderer");
+            for renderer in Renderer::ALL {
+                let text = format!("{renderer:?}");
+                ui.radio_value(&mut self.config.renderer, renderer, text);
+            }
+        });
+
+        let selected = match &self.config.theme.pieces {
+            PieceSet::Directory(_) => "Directory".to_owned(),
+            pieces => format!("{pieces:?}"),
+        };
+        egui::ComboBox::from_label("Pieces")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for pieces in PieceSet::NAMED {
+                    let text = format!("{pieces:?}");
+                    ui.selectable_value(
+                        &mut self.config.theme.pieces,
+                        pieces,
+                        text,
+                    );
+                }
+            });
+        TextEdit::singleline(&mut self.pieces)
+            .hint_text("Piece set directory (empty for builtin)")
+            .show(ui);
+        if ui.add(Button::new("Load pieces")).clicked() {
+            if self.pieces.is_empty() {
+                self.config.theme.pieces = PieceSet::Builtin;
+                self.pieces_error = None;
+            } else {
+                match PieceSet::directory(&self.pieces) {
+                    Ok(pieces) => {
+                        self.config.theme.pieces = pieces;
+                        self.pieces_error = None;
+                    }
+                    Err(e) => self.pieces_error = Some(e.to_string()),
+                }
+            }
+        }
+        if let Some(e) = &self.pieces_error {
+            ui.label(e.as_str());
+        }
+
+        let old_scheduler = self.deck.scheduler.clone();
+        ui.horizontal(|ui| {
+            ui.label("Scheduler");
+            ui.radio_value(
+                &mut self.deck.scheduler,
+                SchedulerKind::Sm2,
+                "SM-2",
+            );
+            if ui
+                .radio(
+                    matches!(self.deck.scheduler, SchedulerKind::Fsrs(_)),
+                    "FSRS",
+                )
+                .clicked()
+                && !matches!(self.deck.scheduler, SchedulerKind::Fsrs(_))
+            {
+                self.deck.scheduler = SchedulerKind::Fsrs(Fsrs::default());
+            }
+        });
+        if let SchedulerKind::Fsrs(fsrs) = &mut self.deck.scheduler {
+            ui.add(
+                egui::Slider::new(&mut fsrs.desired_retention, 0.7..=0.99)
+                    .text("Desired retention"),
+            );
+        }
+        if self.deck.scheduler != old_scheduler {
+            self.save();
+        }
+
+        let old_limits = self.deck.limits;
+        let limits = &mut self.deck.limits;
+        ui.add(
+            egui::Slider::new(&mut limits.new_per_day, 0..=100)
+                .text("New cards per day"),
+        );
+        ui.add(
+            egui::Slider::new(&mut limits.reviews_per_day, 0..=1000)
+                .text("Reviews per day"),
+        );
+        ui.add(
+            egui::Slider::new(&mut limits.day_start, 0..=23)
+                .text("Hour the day starts (UTC)"),
+        );
+        if self.deck.limits != old_limits {
+            self.save();
+        }
+
+        if self.config != old {
+            // force the current card to be redrawn in the new style
+            self.cur_card = None;
+            self.line = None;
+            if let Err(e) = self.config.save_to(Config::default_path()) {
+                self.error = Some(format!("failed to save the config: {e}"));
+            }
+        }
+        self.show_error(ui);
+        if ui.add(Button::new("Done")).clicked() {
+            self.view = Self::main_view;
+        }
     }
 
     fn add_view(&mut self, ui: &mut egui::Ui) {
         self.add_card(ui);
 
         if ui.add(Button::new("Add")).clicked() {
-            self.deck.push(Card::new(
-                &Pgn::from_str(&self.pgn).unwrap(),
-                self.half_move.parse().unwrap(),
-                "card.png".to_owned(),
-                std::mem::take(&mut self.answer),
-            ));
-            self.deck.dump(DECK_PATH).unwrap();
-            self.pgn.clear();
-            self.half_move.clear();
-            self.some_review = true;
+            match self.new_card() {
+                Ok(card) => {
+                    self.deck.push(card);
+                    self.save();
+                    self.pgn.clear();
+                    self.half_move.clear();
+                    self.answer.clear();
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
         }
+        self.show_error(ui);
 
         if ui.add(Button::new("Done")).clicked() {
             self.view = Self::main_view;
@@ -120,116 +551,269 @@ impl App {
 
         let answer = TextEdit::singleline(&mut self.answer).hint_text("Answer");
         answer.show(ui);
+
+        ui.horizontal(|ui| {
+            ui.label("Orientation");
+            ui.radio_value(&mut self.orientation, None, "Side to move");
+            ui.radio_value(&mut self.orientation, Some(Color::White), "White");
+            ui.radio_value(&mut self.orientation, Some(Color::Black), "Black");
+        });
+    }
+
+    /// build a [Card] from the inputs in [Self::add_card]
+    fn new_card(&self) -> Result<Card, Box<dyn Error>> {
+        let pgn = Pgn::from_str(&self.pgn).map_err(|_| "invalid PGN")?;
+        let half_move = self
+            .half_move
+            .parse()
+            .map_err(|_| format!("invalid half move {:?}", self.half_move))?;
+        let mut card = Card::new(&pgn, half_move, self.answer.clone())?;
+        if let Some(pos) = &mut card.position {
+            pos.orientation = self.orientation;
+        }
+        Ok(card)
+    }
+
+    /// display [Self::error], if there is one
+    fn show_error(&self, ui: &mut Ui) {
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e.as_str());
+        }
     }
 
     fn edit_view(&mut self, ui: &mut egui::Ui) {
-        let idx = self.cur_card_index();
-        self.show_card(ui, idx);
+        let Some(id) = self.cur_card_id() else {
+            ui.label("The deck is empty");
+            if ui.add(Button::new("Done")).clicked() {
+                self.view = Self::main_view;
+            }
+            return;
+        };
+        self.show_card(ui, id);
+        self.edit_tags(ui, id);
         self.add_card(ui);
         if ui.add(Button::new("Update")).clicked() {
-            self.deck.cards[0] = Card::new(
-                &Pgn::from_str(&self.pgn).unwrap(),
-                self.half_move.parse().unwrap(),
-                "card.png".to_owned(),
-                std::mem::take(&mut self.answer),
-            );
-            self.deck.dump(DECK_PATH).unwrap();
-            self.pgn.clear();
-            self.half_move.clear();
-            self.some_review = true;
+            match self.new_card() {
+                Ok(card) => {
+                    self.deck.update(id, card);
+                    self.save();
+                    // redraw the edited card
+                    self.cur_card = None;
+                    self.line = None;
+                    self.pgn.clear();
+                    self.half_move.clear();
+                    self.answer.clear();
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
         }
+        self.show_error(ui);
+        self.card_state(ui, id);
         if ui.add(Button::new("Done")).clicked() {
             self.view = Self::main_view;
         }
+        let Some(idx) = self.deck.index_of(id) else {
+            return;
+        };
         if ui
-            .add_enabled(idx < self.deck.cards.len() - 1, Button::new("Next"))
+            .add_enabled(idx + 1 < self.deck.cards.len(), Button::new("Next"))
             .clicked()
         {
-            self.show_card(ui, idx + 1);
+            self.show_card(ui, self.deck.cards[idx + 1].id);
         }
         if ui.add_enabled(idx > 0, Button::new("Prev")).clicked() {
-            self.show_card(ui, idx - 1);
+            self.show_card(ui, self.deck.cards[idx - 1].id);
+        }
+    }
+
+    /// an input for the tags of the card with `id`, separated by spaces
+    fn edit_tags(&mut self, ui: &mut Ui, id: CardId) {
+        if self.tags_card != Some(id) {
+            self.tags = self
+                .deck
+                .get(id)
+                .map(|c| c.tags.join(" "))
+                .unwrap_or_default();
+            self.tags_card = Some(id);
         }
+        ui.horizontal(|ui| {
+            TextEdit::singleline(&mut self.tags)
+                .hint_text("Tags")
+                .show(ui);
+            if ui.add(Button::new("Set tags")).clicked() {
+                if let Some(card) = self.deck.get_mut(id) {
+                    card.tags = self
+                        .tags
+                        .split_whitespace()
+                        .map(str::to_owned)
+                        .collect();
+                    self.save();
+                }
+            }
+        });
+    }
+
+    /// buttons to suspend, bury, or delete the card with `id`
+    fn card_state(&mut self, ui: &mut Ui, id: CardId) {
+        let Some(card) = self.deck.get(id) else {
+            return;
+        };
+        let suspended = card.suspended;
+        let buried = !suspended && !self.deck.is_active(card);
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let text = if suspended { "Unsuspend" } else { "Suspend" };
+            if ui.add(Button::new(text)).clicked() {
+                changed = self.deck.suspend(id, !suspended);
+            }
+            let text = if buried {
+                "Unbury"
+            } else {
+                "Bury until tomorrow"
+            };
+            if ui.add_enabled(!suspended, Button::new(text)).clicked() {
+                changed = self.deck.bury(id, !buried);
+            }
+            if ui.add(Button::new("Delete")).clicked() {
+                changed = self.deck.remove(id).is_some();
+                // show the first card instead
+                self.cur_card = None;
+                self.line = None;
+            }
+        });
+        if changed {
+            self.save();
+        }
+    }
+
+    /// the cards to review that match [Self::filter]
+    fn review_queue(&self) -> Result<Vec<CardId>, FilterError> {
+        if self.filter.trim().is_empty() {
+            return Ok(self.deck.queue());
+        }
+        Ok(self.deck.queue_matching(&self.filter.parse()?))
     }
 
     fn review_prompt(&mut self, ui: &mut egui::Ui) {
-        let mut cards: Vec<usize> = self
-            .deck
-            .cards
-            .iter()
-            .enumerate()
-            .filter_map(|(i, c)| if c.is_due() { Some(i) } else { None })
-            .collect();
-        cards.shuffle(&mut rand::thread_rng());
-        if let Some(i) = cards.first() {
-            self.show_card(ui, *i);
+        let cards = self.review_queue().unwrap_or_default();
+        if let Some(&id) = cards.first() {
+            if self.shown_at.is_none() {
+                self.shown_at = Some(Instant::now());
+            }
+            self.show_card(ui, id);
             if ui.add(Button::new("Check")).clicked() {
                 self.view = Self::review_answer;
             }
         } else {
-            self.some_review = false;
             self.view = Self::main_view;
         }
     }
 
-    fn show_card(&mut self, ui: &mut Ui, index: usize) {
-        let idx = self.cur_card_index();
-        if self.cur_card.is_none() || index != idx {
-            self.cur_card = Some(CardImage {
-                texture: ui.ctx().load_texture(
-                    "the card",
-                    load_image(&self.deck.cards[index].pgn).unwrap(),
-                    TextureOptions::default(),
-                ),
-                index,
-            });
+    fn show_card(&mut self, ui: &mut Ui, id: CardId) {
+        if self.cur_card.as_ref().is_none_or(|c| c.id != id) {
+            let Some(card) = self.deck.get(id) else {
+                return;
+            };
+            let image = card
+                .render_cached(
+                    &self.cache,
+                    self.config.renderer,
+                    &self.config.theme,
+                    DEFAULT_SIZE,
+                )
+                .map_err(|e| e.to_string())
+                .and_then(|png| load_image(&png).map_err(|e| e.to_string()));
+            let texture = match image {
+                Ok(image) => {
+                    self.error = None;
+                    Some(ui.ctx().load_texture(
+                        "the card",
+                        image,
+                        TextureOptions::default(),
+                    ))
+                }
+                Err(e) => {
+                    self.error = Some(format!("failed to draw card: {e}"));
+                    None
+                }
+            };
+            self.cur_card = Some(CardImage { texture, id });
+        }
+        match &self.cur_card.as_ref().unwrap().texture {
+            Some(texture) => {
+                ui.image(texture, [320., 320.]);
+            }
+            None => self.show_error(ui),
         }
-        ui.image(&self.cur_card.as_ref().unwrap().texture, [320., 320.]);
     }
 
-    fn review_answer(&mut self, ui: &mut egui::Ui) {
-        let idx = self.cur_card_index();
-        self.show_card(ui, idx);
-        ui.label(format!("Answer: {}", self.deck.cards[0].answer));
-        let idx = self.cur_card_index();
-        if ui.add(Button::new("0")).clicked() {
-            self.deck.cards[idx].update_card(Quality::Zero);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
+    /// play the moves leading to the card with `id` as an animation, with
+    /// the same timing as [chanki::deck::Position::line_gif]
+    fn show_line(&mut self, ui: &mut Ui, id: CardId) {
+        if self.line.as_ref().is_none_or(|l| l.id != id) {
+            let pngs = match self.deck.get(id).and_then(|c| c.position.as_ref())
+            {
+                Some(pos) => pos
+                    .line_pngs(&self.config.theme, DEFAULT_SIZE)
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+            let frames = pngs
+                .iter()
+                .filter_map(|png| load_image(png).ok())
+                .enumerate()
+                .map(|(i, image)| {
+                    ui.ctx().load_texture(
+                        format!("line {i}"),
+                        image,
+                        TextureOptions::default(),
+                    )
+                })
+                .collect();
+            self.line = Some(LineAnimation { frames, id });
         }
-        if ui.add(Button::new("1")).clicked() {
-            self.deck.cards[idx].update_card(Quality::One);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
-        }
-        if ui.add(Button::new("2")).clicked() {
-            self.deck.cards[idx].update_card(Quality::Two);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
-        }
-        if ui.add(Button::new("3")).clicked() {
-            self.deck.cards[idx].update_card(Quality::Three);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
-        }
-        if ui.add(Button::new("4")).clicked() {
-            self.deck.cards[idx].update_card(Quality::Four);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
+        let frames = &self.line.as_ref().unwrap().frames;
+        let Some(last) = frames.len().checked_sub(1) else {
+            return;
+        };
+        let cycle = last as f64 * FRAME_DELAY as f64 + FINAL_DELAY as f64;
+        let t = (ui.ctx().input(|i| i.time) * 100.0) % cycle;
+        let frame = ((t / FRAME_DELAY as f64) as usize).min(last);
+        ui.image(&frames[frame], [240., 240.]);
+        ui.ctx().request_repaint_after(Duration::from_millis(50));
+    }
+
+    fn review_answer(&mut self, ui: &mut egui::Ui) {
+        let Some(id) = self.cur_card_id() else {
+            self.view = Self::main_view;
+            return;
+        };
+        self.show_card(ui, id);
+        if let Some(card) = self.deck.get(id) {
+            ui.label(format!("Answer: {}", card.answer));
         }
-        if ui.add(Button::new("5")).clicked() {
-            self.deck.cards[idx].update_card(Quality::Five);
-            self.deck.dump(DECK_PATH).unwrap();
-            self.view = Self::review_prompt;
+        self.show_line(ui, id);
+        for q in 0..=5 {
+            if ui.add(Button::new(q.to_string())).clicked() {
+                let taken = self
+                    .shown_at
+                    .take()
+                    .map_or(Duration::ZERO, |t| t.elapsed());
+                self.deck.answer(id, Quality::try_from(q).unwrap(), taken);
+                self.save();
+                self.view = Self::review_prompt;
+            }
         }
     }
 
-    fn cur_card_index(&self) -> usize {
-        if let Some(c) = &self.cur_card {
-            c.index
-        } else {
-            0
-        }
+    /// the card being shown, or the first in the deck if there isn't one
+    fn cur_card_id(&self) -> Option<CardId> {
+        self.cur_card
+            .as_ref()
+            .map(|c| c.id)
+            .filter(|&id| self.deck.get(id).is_some())
+            .or_else(|| self.deck.cards.first().map(|c| c.id))
     }
 }
 
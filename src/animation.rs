@@ -0,0 +1,114 @@
+//! animations of the moves leading up to a card's position
+
+use std::str::FromStr;
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{
+    annotation::{Brush, Shape},
+    board::Board,
+    deck::Position,
+    pgn::Pgn,
+    png::{svg_to_pixmap, svg_to_png},
+    render::RenderError,
+    theme::Theme,
+};
+
+/// how long each move is shown, in hundredths of a second
+pub const FRAME_DELAY: u16 = 100;
+
+/// how long the final position is held before the animation repeats, in
+/// hundredths of a second
+pub const FINAL_DELAY: u16 = 300;
+
+impl Position {
+    /// the boards along the line from the starting position to `self`, each
+    /// with the shapes to draw on it: an arrow for the move just played, and
+    /// all of `self`'s shapes on the last one. returns
+    /// [RenderError::Position] for positions not taken from a PGN
+    pub fn line(&self) -> Result<Vec<(Board, Vec<Shape>)>, RenderError> {
+        let (Some(pgn), Some(halfmove)) = (&self.pgn, self.halfmove) else {
+            return Err(RenderError::Position(
+                "no game to animate the position from".to_owned(),
+            ));
+        };
+        let pgn = Pgn::from_str(pgn)
+            .map_err(|e| RenderError::Position(e.to_string()))?;
+        let mut frames = vec![(Board::new(), Vec::new())];
+        for n in 1..=halfmove {
+            let mut board = Board::new();
+            let moves = board.play(&pgn, n);
+            let (from, to) = *moves.last().ok_or_else(|| {
+                RenderError::Position(format!("move {n} is not in the game"))
+            })?;
+            let arrow = Shape::Arrow {
+                from,
+                to,
+                brush: Brush::Green,
+            };
+            frames.push((board, vec![arrow]));
+        }
+        if let Some((_, shapes)) = frames.last_mut() {
+            *shapes = self.all_shapes();
+        }
+        Ok(frames)
+    }
+
+    /// render each board of [Self::line] as a PNG with sides of `size`
+    /// pixels. every frame is drawn with the orientation of `self`
+    pub fn line_pngs(
+        &self,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<Vec<u8>>, RenderError> {
+        let flip = self.flip(&self.board()?);
+        self.line()?
+            .iter()
+            .map(|(board, shapes)| {
+                svg_to_png(&board.to_svg(shapes, flip, theme), size)
+            })
+            .collect()
+    }
+
+    /// render [Self::line] as a looping animated GIF with sides of `size`
+    /// pixels
+    pub fn line_gif(
+        &self,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        let err = |e: gif::EncodingError| RenderError::Raster(e.to_string());
+        let flip = self.flip(&self.board()?);
+        let line = self.line()?;
+        let side = u16::try_from(size).map_err(|_| {
+            RenderError::Raster(format!("invalid image size {size}"))
+        })?;
+        let mut gif = Vec::new();
+        {
+            let mut encoder =
+                Encoder::new(&mut gif, side, side, &[]).map_err(err)?;
+            encoder.set_repeat(Repeat::Infinite).map_err(err)?;
+            for (i, (board, shapes)) in line.iter().enumerate() {
+                let pixmap =
+                    svg_to_pixmap(&board.to_svg(shapes, flip, theme), size)?;
+                let mut rgba: Vec<u8> = pixmap
+                    .pixels()
+                    .iter()
+                    .flat_map(|p| {
+                        let c = p.demultiply();
+                        [c.red(), c.green(), c.blue(), c.alpha()]
+                    })
+                    .collect();
+                let mut frame =
+                    Frame::from_rgba_speed(side, side, &mut rgba, 10);
+                frame.delay = if i + 1 == line.len() {
+                    FINAL_DELAY
+                } else {
+                    FRAME_DELAY
+                };
+                encoder.write_frame(&frame).map_err(err)?;
+            }
+        }
+        Ok(gif)
+    }
+}
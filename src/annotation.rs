@@ -0,0 +1,132 @@
+//! arrows and square markings drawn on top of a diagram, in the style of
+//! Lichess's annotation shapes
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Coord;
+
+/// the colors available for [Shape]s. these match the four Lichess brushes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Brush {
+    #[default]
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl Brush {
+    /// parse the single-letter color code used in `[%cal]` and `[%csl]`
+    /// comments
+    pub fn from_code(c: char) -> Option<Self> {
+        match c {
+            'G' => Some(Brush::Green),
+            'R' => Some(Brush::Red),
+            'Y' => Some(Brush::Yellow),
+            'B' => Some(Brush::Blue),
+            _ => None,
+        }
+    }
+
+    /// the hex color used for `self` in SVG diagrams
+    pub fn hex(&self) -> &'static str {
+        match self {
+            Brush::Green => "#15781b",
+            Brush::Red => "#882020",
+            Brush::Yellow => "#e68f00",
+            Brush::Blue => "#003088",
+        }
+    }
+
+    /// the xcolor name used for `self` in LaTeX diagrams
+    pub fn latex(&self) -> &'static str {
+        match self {
+            Brush::Green => "green",
+            Brush::Red => "red",
+            Brush::Yellow => "yellow",
+            Brush::Blue => "blue",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    /// an arrow from the center of one square to another
+    Arrow {
+        from: Coord,
+        to: Coord,
+        brush: Brush,
+    },
+
+    /// a ring drawn inside of a square
+    Circle { square: Coord, brush: Brush },
+
+    /// a translucent fill over a whole square
+    Highlight { square: Coord, brush: Brush },
+}
+
+/// parse a square like `e4` into a [Coord]
+pub fn parse_square(s: &str) -> Option<Coord> {
+    let mut chars = s.chars();
+    let file = chars.next().filter(|c| ('a'..='h').contains(c))?;
+    let rank = chars.next()?.to_digit(10).filter(|r| (1..=8).contains(r))?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some((file, rank as usize - 1))
+}
+
+/// parse a comma-separated list of arrows like `Ge2e4,Rd7d5`, as found in
+/// `[%cal]` comments
+pub fn parse_arrows(s: &str) -> Option<Vec<Shape>> {
+    s.split(',')
+        .map(|a| {
+            let a = a.trim();
+            let brush = Brush::from_code(a.chars().next()?)?;
+            let from = parse_square(a.get(1..3)?)?;
+            let to = parse_square(a.get(3..)?)?;
+            Some(Shape::Arrow { from, to, brush })
+        })
+        .collect()
+}
+
+/// parse a comma-separated list of squares like `Gd4,Re5` into shapes built
+/// by `shape`
+pub fn parse_squares(
+    s: &str,
+    shape: fn(Coord, Brush) -> Shape,
+) -> Option<Vec<Shape>> {
+    s.split(',')
+        .map(|sq| {
+            let sq = sq.trim();
+            let brush = Brush::from_code(sq.chars().next()?)?;
+            Some(shape(parse_square(sq.get(1..)?)?, brush))
+        })
+        .collect()
+}
+
+/// extract the shapes from any `[%cal ...]` and `[%csl ...]` commands in a
+/// PGN comment. as on Lichess, `%csl` squares are drawn as circles. malformed
+/// commands are skipped
+pub fn parse_comment(comment: &str) -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        let Some(end) = rest[start..].find(']') else {
+            break;
+        };
+        let command = &rest[start + 2..start + end];
+        rest = &rest[start + end + 1..];
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        let parsed = match name {
+            "cal" => parse_arrows(args),
+            "csl" => parse_squares(args, |square, brush| Shape::Circle {
+                square,
+                brush,
+            }),
+            _ => None,
+        };
+        shapes.extend(parsed.unwrap_or_default());
+    }
+    shapes
+}
@@ -0,0 +1,140 @@
+//! saving decks so that a crash can't lose them, and the backups kept of
+//! them. the backups of `dir/name.json` go in `dir/.backups/name`, named by
+//! the time they were taken
+
+use std::{
+    cmp::Reverse,
+    error::Error,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+
+use crate::deck::Deck;
+
+/// how many backups of each file to keep
+pub const KEPT: usize = 10;
+
+/// the least time in minutes between backups of a file, so that a long review
+/// session doesn't push out all of the older backups
+pub const INTERVAL: i64 = 60;
+
+/// the format of backup file names, which sorts them by time
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// a copy of a file taken at `time`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    pub time: DateTime<Utc>,
+    pub path: PathBuf,
+}
+
+/// the directory containing `path`, which is `.` for a bare file name
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// replace the contents of `path` with `contents` so that a crash leaves
+/// either the old file or the new one, by writing to a temporary file in the
+/// same directory, syncing it, and renaming it over `path`
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let Some(name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        ));
+    };
+    let dir = parent(path);
+    let tmp = dir.join(format!(".{}.tmp", name.to_string_lossy()));
+    let result = File::create(&tmp)
+        .and_then(|mut f| {
+            f.write_all(contents)?;
+            f.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+    // sync the directory too so that the rename survives a crash. not every
+    // platform can open directories, so this is best effort
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// the directory holding the backups of `path`
+pub fn dir(path: &Path) -> PathBuf {
+    let name = path.file_stem().unwrap_or_default();
+    parent(path).join(".backups").join(name)
+}
+
+/// the backups of `path`, newest first
+pub fn list(path: &Path) -> io::Result<Vec<Backup>> {
+    let entries = match fs::read_dir(dir(path)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file = entry.file_name();
+        let Some(stem) = file.to_str().and_then(|f| f.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        if let Ok(time) = NaiveDateTime::parse_from_str(stem, TIME_FORMAT) {
+            backups.push(Backup {
+                time: Utc.from_utc_datetime(&time),
+                path: entry.path(),
+            });
+        }
+    }
+    backups.sort_by_key(|b| Reverse(b.time));
+    Ok(backups)
+}
+
+/// copy `path` to a new backup taken at `now`, unless it doesn't exist or
+/// the last backup is less than [INTERVAL] minutes old and `force` is false.
+/// only the newest [KEPT] backups are kept
+pub fn back_up(path: &Path, now: DateTime<Utc>, force: bool) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let recent = list(path)?
+        .first()
+        .is_some_and(|b| now - b.time < Duration::minutes(INTERVAL));
+    if recent && !force {
+        return Ok(());
+    }
+    let dir = dir(path);
+    fs::create_dir_all(&dir)?;
+    let backup = dir.join(format!("{}.json", now.format(TIME_FORMAT)));
+    write_atomic(&backup, &fs::read(path)?)?;
+    for old in list(path)?.into_iter().skip(KEPT) {
+        fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+/// replace the deck at `path` with `backup`, after backing up the deck as it
+/// is at `now` so that the restore can be undone
+pub fn restore(
+    path: &Path,
+    backup: &Backup,
+    now: DateTime<Utc>,
+) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read(&backup.path)?;
+    // make sure that the backup is a deck before replacing anything
+    serde_json::from_slice::<Deck>(&contents)?;
+    back_up(path, now, true)?;
+    write_atomic(path, &contents)?;
+    Ok(())
+}
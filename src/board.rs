@@ -1,15 +1,21 @@
 use std::{
+    error::Error,
     fmt::Display,
     ops::{Index, IndexMut},
+    str::FromStr,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    annotation::Shape,
     pgn::{Move, Pgn},
+    theme::{Rgb, Theme},
     DEBUG,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-enum PieceType {
+pub(crate) enum PieceType {
     King,
     Queen,
     Rook,
@@ -18,6 +24,17 @@ enum PieceType {
     Pawn,
 }
 
+impl PieceType {
+    pub(crate) const ALL: [PieceType; 6] = [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Pawn,
+    ];
+}
+
 impl Display for PieceType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{self:?}")
@@ -38,7 +55,7 @@ impl From<char> for PieceType {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Color {
     White,
     Black,
@@ -52,8 +69,8 @@ impl Display for Color {
 
 #[derive(Clone, Copy, Debug)]
 pub struct Piece {
-    typ: PieceType,
-    color: Color,
+    pub(crate) typ: PieceType,
+    pub(crate) color: Color,
 }
 
 impl Piece {
@@ -96,6 +113,9 @@ impl Piece {
 
 pub struct Board {
     squares: [[Option<Piece>; 8]; 8],
+
+    /// the side whose turn it is to move
+    pub to_move: Color,
 }
 
 macro_rules! black {
@@ -131,6 +151,7 @@ impl Board {
                 white!(Pawn, Pawn, Pawn, Pawn, Pawn, Pawn, Pawn, Pawn,),
                 white!(Rook, Knight, Bishop, Queen, King, Bishop, Knight, Rook),
             ],
+            to_move: Color::White,
         }
     }
 
@@ -148,6 +169,7 @@ impl Board {
                 print!("{turn:>3}. ");
             }
             moves.push(self.mov(white, Color::White));
+            self.to_move = Color::Black;
             i += 1;
             if i == move_number {
                 return moves;
@@ -156,6 +178,7 @@ impl Board {
                 print!(" ... ");
             }
             moves.push(self.mov(black, Color::Black));
+            self.to_move = Color::White;
             i += 1;
             if i == move_number {
                 return moves;
@@ -164,18 +187,57 @@ impl Board {
         moves
     }
 
-    pub fn to_latex(&self, (from, to): (Coord, Coord)) -> String {
-        let (ff, fr) = from;
-        let (tf, tr) = to;
-        let (fr, tr) = (fr + 1, tr + 1);
+    /// render `self` as a LaTeX document with xskak, with `shapes` drawn on
+    /// top. if `flip` is true, the board is drawn from Black's perspective.
+    /// only the square colors and coordinates are taken from `theme`, since
+    /// xskak has its own piece fonts and frame
+    pub fn to_latex(
+        &self,
+        shapes: &[Shape],
+        flip: bool,
+        theme: &Theme,
+    ) -> String {
+        let (light, dark) = theme.board.colors();
+        // xcolor's HTML model takes the hex digits without the #
+        let html = |c: Rgb| format!("{:02x}{:02x}{:02x}", c.0, c.1, c.2);
+        let (light, dark) = (html(light), html(dark));
+        let label = theme.coordinates;
+        let square = |(f, r): Coord| format!("{f}{}", r + 1);
+        let mut marks = String::new();
+        for shape in shapes {
+            // chessboard applies its keys in order, so each mark can set its
+            // own style and color
+            let mark = match *shape {
+                Shape::Arrow { from, to, brush } => format!(
+                    "pgfstyle=straightmove, color={}, markmoves={{{}-{}}}",
+                    brush.latex(),
+                    square(from),
+                    square(to)
+                ),
+                Shape::Circle { square: sq, brush } => format!(
+                    "pgfstyle=circle, color={}, markfields={{{}}}",
+                    brush.latex(),
+                    square(sq)
+                ),
+                Shape::Highlight { square: sq, brush } => format!(
+                    "pgfstyle=color, opacity=0.5, color={}, markfields={{{}}}",
+                    brush.latex(),
+                    square(sq)
+                ),
+            };
+            marks.push_str(",\n");
+            marks.push_str(&mark);
+        }
         format!(
             r#"
 \documentclass{{standalone}}
 \usepackage{{xskak}}
+\definecolor{{chankilight}}{{HTML}}{{{light}}}
+\definecolor{{chankidark}}{{HTML}}{{{dark}}}
 \begin{{document}}
 \newchessgame
-\chessboard[setfen={self}, showmover=false,
-pgfstyle=straightmove, markmoves={{{ff}{fr}-{tf}{tr}}}]
+\chessboard[setfen={self}, showmover=false, inverse={flip}, label={label},
+colorwhitebackfields=chankilight, colorblackbackfields=chankidark{marks}]
 \end{{document}}
 "#
         )
@@ -382,7 +444,7 @@ impl Default for Board {
 }
 
 /// convert the char file to a usize
-const fn to_idx(c: char) -> usize {
+pub(crate) const fn to_idx(c: char) -> usize {
     c as usize - 'a' as usize
 }
 
@@ -438,9 +500,70 @@ impl Display for Board {
             empty = 0;
             write!(f, "{}", if i < 7 { "/" } else { " " })?;
         }
-        // for now just say it's white's move, all castling possible, no en
-        // passant targets, no halfmoves since capture or pawn advance, and
-        // move 1
-        write!(f, "w KQkq - 0 1")
+        let to_move = match self.to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        // for now just say all castling is possible, no en passant targets,
+        // no halfmoves since capture or pawn advance, and move 1
+        write!(f, "{to_move} KQkq - 0 1")
+    }
+}
+
+#[derive(Debug)]
+pub struct FenError;
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for FenError {}
+
+impl FromStr for Board {
+    type Err = FenError;
+
+    /// parse the piece placement and active color fields of a FEN string.
+    /// the remaining fields are ignored, as in the [Display] impl
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_ascii_whitespace();
+        let placement = fields.next().ok_or(FenError)?;
+        let mut squares = [[None; 8]; 8];
+        let rows: Vec<_> = placement.split('/').collect();
+        if rows.len() != 8 {
+            return Err(FenError);
+        }
+        for (row, squares) in rows.iter().zip(squares.iter_mut()) {
+            let mut col = 0;
+            for c in row.chars() {
+                if let Some(n) = c.to_digit(10) {
+                    col += n as usize;
+                    continue;
+                }
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                let typ = match c.to_ascii_uppercase() {
+                    'P' => PieceType::Pawn,
+                    c @ ('R' | 'N' | 'B' | 'Q' | 'K') => PieceType::from(c),
+                    _ => return Err(FenError),
+                };
+                *squares.get_mut(col).ok_or(FenError)? =
+                    Some(Piece { typ, color });
+                col += 1;
+            }
+            if col != 8 {
+                return Err(FenError);
+            }
+        }
+        let to_move = match fields.next() {
+            Some("w") | None => Color::White,
+            Some("b") => Color::Black,
+            Some(_) => return Err(FenError),
+        };
+        Ok(Self { squares, to_move })
     }
 }
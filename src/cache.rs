@@ -0,0 +1,128 @@
+//! a content-addressed on-disk cache of rendered diagrams, so that the same
+//! position in the same style is only rendered once
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::render::RenderError;
+
+/// the default limit on the total size of a [RenderCache], in bytes
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+pub struct RenderCache {
+    dir: PathBuf,
+
+    /// once the cached files add up to more than this many bytes, the least
+    /// recently used are deleted
+    max_bytes: u64,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(Self::default_dir(), DEFAULT_MAX_BYTES)
+    }
+}
+
+impl RenderCache {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/chanki/renders`, falling back on `~/.cache` and then
+    /// the temporary directory
+    pub fn default_dir() -> PathBuf {
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".cache"),
+                None => std::env::temp_dir(),
+            },
+        };
+        base.join("chanki").join("renders")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// the path of the file cached under `key`
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.png", fnv1a(key.as_bytes())))
+    }
+
+    /// return the image cached under `key`, or call `render` to produce it
+    /// and store the result. `key` should describe everything that affects
+    /// the rendered image. failing to read or write the cache is not an
+    /// error, just a miss
+    pub fn get_or_render(
+        &self,
+        key: &str,
+        render: impl FnOnce() -> Result<Vec<u8>, RenderError>,
+    ) -> Result<Vec<u8>, RenderError> {
+        let path = self.path(key);
+        if let Ok(png) = fs::read(&path) {
+            // mark the file as recently used for eviction
+            if let Ok(f) = File::options().append(true).open(&path) {
+                let _ = f.set_modified(SystemTime::now());
+            }
+            return Ok(png);
+        }
+        let png = render()?;
+        if self.store(&path, &png).is_ok() {
+            let _ = self.evict();
+        }
+        Ok(png)
+    }
+
+    /// write `png` to `path` via a temporary file, so that a concurrent
+    /// reader never sees a partial image
+    fn store(&self, path: &Path, png: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, png)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// delete the least recently used images until the cache fits in
+    /// `self.max_bytes`
+    pub fn evict(&self) -> io::Result<()> {
+        let mut files = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.path().extension().is_none_or(|e| e != "png") {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            total += meta.len();
+            files.push((meta.modified()?, meta.len(), entry.path()));
+        }
+        files.sort();
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+/// the 64-bit FNV-1a hash of `bytes`. unlike [std::hash::DefaultHasher], it
+/// is stable across Rust versions, which matters for file names on disk
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,328 @@
+//! a collection of named decks, stored as one JSON file each. decks can be
+//! nested as sub-decks by separating the parts of their names with `/`, like
+//! `white/sicilian`, which is stored as `white/sicilian.json` inside the
+//! collection's directory. nesting only groups the names: a sub-deck is a
+//! separate deck with its own cards and settings, and reviewing, listing, or
+//! forecasting its parent leaves it out. renaming and deleting are the only
+//! operations that apply to sub-decks along with their parent
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{backup, deck::Deck, DECK_PATH};
+
+/// the deck created in a new collection
+pub const DEFAULT_DECK: &str = "default";
+
+/// the file in the collection's directory holding the name of the current
+/// deck. it starts with `.` so that it can't clash with a deck's sub-deck
+/// directory
+const CURRENT_FILE: &str = ".current";
+
+#[derive(Debug)]
+pub enum CollectionError {
+    /// a deck name that is empty, has empty parts, or would refer to a file
+    /// outside of the collection
+    InvalidName(String),
+
+    /// there is no deck with this name
+    NotFound(String),
+
+    /// there is already a deck with this name
+    Exists(String),
+
+    Io(io::Error),
+
+    /// a deck file that couldn't be read or written
+    Deck(Box<dyn Error>),
+}
+
+impl Display for CollectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollectionError::InvalidName(name) => {
+                write!(f, "invalid deck name {name:?}")
+            }
+            CollectionError::NotFound(name) => {
+                write!(f, "no deck named {name}")
+            }
+            CollectionError::Exists(name) => {
+                write!(f, "a deck named {name} already exists")
+            }
+            CollectionError::Io(e) => write!(f, "{e}"),
+            CollectionError::Deck(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CollectionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CollectionError::Io(e) => Some(e),
+            CollectionError::Deck(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CollectionError {
+    fn from(e: io::Error) -> Self {
+        CollectionError::Io(e)
+    }
+}
+
+pub struct Collection {
+    dir: PathBuf,
+
+    /// the deck whose settings new decks are given
+    template: Deck,
+}
+
+impl Collection {
+    /// open the collection in `dir`, creating it if it doesn't exist. a new
+    /// collection starts with the deck at [DECK_PATH] if there is one, from
+    /// before decks were kept in collections, and an empty deck otherwise,
+    /// both named [DEFAULT_DECK]
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, CollectionError> {
+        Self::open_with(dir, Deck::default())
+    }
+
+    /// [Self::open], giving new decks the settings of `template`, such as its
+    /// scheduler and limits. its cards and reviews aren't copied
+    pub fn open_with(
+        dir: impl Into<PathBuf>,
+        template: Deck,
+    ) -> Result<Self, CollectionError> {
+        let collection = Self {
+            dir: dir.into(),
+            template,
+        };
+        fs::create_dir_all(&collection.dir)?;
+        if collection.names()?.is_empty() {
+            let path = collection.path(DEFAULT_DECK);
+            if Path::new(DECK_PATH).exists() {
+                fs::copy(DECK_PATH, path)?;
+            } else {
+                collection.save(DEFAULT_DECK, &collection.new_deck())?;
+            }
+        }
+        Ok(collection)
+    }
+
+    /// an empty deck with the collection's settings for new decks
+    fn new_deck(&self) -> Deck {
+        Deck {
+            cards: Vec::new(),
+            reviews: Vec::new(),
+            next_id: 0,
+            ..self.template.clone()
+        }
+    }
+
+    /// `$XDG_DATA_HOME/chanki/decks`, falling back on `~/.local/share` and
+    /// then the working directory
+    pub fn default_dir() -> PathBuf {
+        let base = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".local").join("share"),
+                None => PathBuf::new(),
+            },
+        };
+        base.join("chanki").join("decks")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// the file storing the deck called `name`, which may not exist
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+
+    /// the directory holding the sub-decks of `name`
+    fn subdeck_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        valid_name(name) && self.path(name).is_file()
+    }
+
+    /// the names of every deck in the collection, sorted so that each deck
+    /// comes right before its sub-decks
+    pub fn names(&self) -> Result<Vec<String>, CollectionError> {
+        let mut names = Vec::new();
+        let mut dirs = vec![(self.dir.clone(), String::new())];
+        while let Some((dir, prefix)) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let Some(file) = entry.file_name().to_str().map(str::to_owned)
+                else {
+                    continue;
+                };
+                // deck names can't start with `.`, which leaves room for
+                // backups and files being saved
+                if file.starts_with('.') {
+                    continue;
+                }
+                if entry.file_type()?.is_dir() {
+                    dirs.push((entry.path(), format!("{prefix}{file}/")));
+                } else if let Some(name) = file.strip_suffix(".json") {
+                    names.push(format!("{prefix}{name}"));
+                }
+            }
+        }
+        // sort by parts so that `a/b` comes before `a-b`
+        names.sort_by(|a, b| a.split('/').cmp(b.split('/')));
+        Ok(names)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Deck, CollectionError> {
+        if !self.contains(name) {
+            return Err(CollectionError::NotFound(name.to_owned()));
+        }
+        Deck::load(self.path(name)).map_err(CollectionError::Deck)
+    }
+
+    pub fn save(&self, name: &str, deck: &Deck) -> Result<(), CollectionError> {
+        check_name(name)?;
+        self.create_parents(name)?;
+        deck.dump(self.path(name)).map_err(CollectionError::Deck)
+    }
+
+    /// add an empty deck called `name`, along with any of its parents that
+    /// don't exist yet
+    pub fn create(&self, name: &str) -> Result<(), CollectionError> {
+        check_name(name)?;
+        if self.contains(name) {
+            return Err(CollectionError::Exists(name.to_owned()));
+        }
+        self.save(name, &self.new_deck())
+    }
+
+    /// create empty decks for the parents of `name` that don't exist
+    fn create_parents(&self, name: &str) -> Result<(), CollectionError> {
+        let mut parent = String::new();
+        let parts: Vec<_> = name.split('/').collect();
+        for part in &parts[..parts.len() - 1] {
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent.push_str(part);
+            fs::create_dir_all(self.subdeck_dir(&parent))?;
+            if !self.contains(&parent) {
+                self.new_deck()
+                    .dump(self.path(&parent))
+                    .map_err(CollectionError::Deck)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// rename the deck `from` to `to`, moving its sub-decks and backups along
+    /// with it
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), CollectionError> {
+        check_name(to)?;
+        if !self.contains(from) {
+            return Err(CollectionError::NotFound(from.to_owned()));
+        }
+        if self.contains(to) || self.subdeck_dir(to).exists() {
+            return Err(CollectionError::Exists(to.to_owned()));
+        }
+        if to.starts_with(&format!("{from}/")) {
+            return Err(CollectionError::InvalidName(to.to_owned()));
+        }
+        let current = self.current()?;
+        self.create_parents(to)?;
+        fs::rename(self.path(from), self.path(to))?;
+        if self.subdeck_dir(from).exists() {
+            fs::rename(self.subdeck_dir(from), self.subdeck_dir(to))?;
+        }
+        // the backups of sub-decks moved with their directory, but the
+        // deck's own are kept beside it
+        let backups = backup::dir(&self.path(from));
+        if backups.exists() {
+            let to = backup::dir(&self.path(to));
+            // backups of no deck, like those of a deck deleted by hand
+            if to.exists() {
+                fs::remove_dir_all(&to)?;
+            }
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(backups, to)?;
+        }
+        if let Some(rest) = current.strip_prefix(from)
+            && (rest.is_empty() || rest.starts_with('/'))
+        {
+            self.switch(&format!("{to}{rest}"))?;
+        }
+        Ok(())
+    }
+
+    /// delete the deck `name` and all of its sub-decks, along with their
+    /// backups so that a new deck of the same name doesn't offer to restore
+    /// them
+    pub fn delete(&self, name: &str) -> Result<(), CollectionError> {
+        if !self.contains(name) {
+            return Err(CollectionError::NotFound(name.to_owned()));
+        }
+        let backups = backup::dir(&self.path(name));
+        if backups.exists() {
+            fs::remove_dir_all(backups)?;
+        }
+        fs::remove_file(self.path(name))?;
+        if self.subdeck_dir(name).exists() {
+            fs::remove_dir_all(self.subdeck_dir(name))?;
+        }
+        Ok(())
+    }
+
+    /// the name of the deck to use when none is given: the one last passed
+    /// to [Self::switch] if it still exists, or else the first deck. returns
+    /// [DEFAULT_DECK] for an empty collection
+    pub fn current(&self) -> Result<String, CollectionError> {
+        if let Ok(name) = fs::read_to_string(self.dir.join(CURRENT_FILE))
+            && self.contains(name.trim())
+        {
+            return Ok(name.trim().to_owned());
+        }
+        Ok(self
+            .names()?
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| DEFAULT_DECK.to_owned()))
+    }
+
+    /// make `name` the current deck
+    pub fn switch(&self, name: &str) -> Result<(), CollectionError> {
+        if !self.contains(name) {
+            return Err(CollectionError::NotFound(name.to_owned()));
+        }
+        Ok(fs::write(self.dir.join(CURRENT_FILE), name)?)
+    }
+}
+
+/// whether `name` can be used for a deck: its `/`-separated parts must be
+/// non-empty and not start with `.`, so that it stays inside the collection
+fn valid_name(name: &str) -> bool {
+    name.split('/').all(|part| {
+        !part.trim().is_empty()
+            && !part.starts_with('.')
+            && !part.contains(['\\', '\0'])
+    })
+}
+
+fn check_name(name: &str) -> Result<(), CollectionError> {
+    if valid_name(name) {
+        Ok(())
+    } else {
+        Err(CollectionError::InvalidName(name.to_owned()))
+    }
+}
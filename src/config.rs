@@ -0,0 +1,144 @@
+//! settings shared by the command line and graphical interfaces, read from
+//! `$XDG_CONFIG_HOME/chanki/config.toml`. every setting is optional, so an
+//! empty or missing file gives the defaults
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collection::{Collection, CollectionError},
+    deck::{Deck, Limits},
+    render::Renderer,
+    scheduler::SchedulerKind,
+    theme::Theme,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// the directory holding the collection of decks, or `None` for
+    /// [Collection::default_dir]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decks: Option<PathBuf>,
+
+    /// how to draw diagrams
+    pub renderer: Renderer,
+
+    /// the style of diagrams
+    pub theme: Theme,
+
+    /// the scheduler given to decks when they're created. existing decks keep
+    /// their own. older files call it `scheduler`
+    #[serde(alias = "scheduler")]
+    pub new_deck_scheduler: SchedulerKind,
+
+    /// how many cards decks give to study each day when they're created.
+    /// existing decks keep their own. older files call it `limits`
+    #[serde(alias = "limits")]
+    pub new_deck_limits: Limits,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+
+    /// the file isn't valid TOML or has settings of the wrong type
+    Parse(PathBuf, toml::de::Error),
+
+    Serialize(toml::ser::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            ConfigError::Parse(path, e) => {
+                write!(f, "invalid config {}: {e}", path.display())
+            }
+            ConfigError::Serialize(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) => Some(e),
+            ConfigError::Parse(_, e) => Some(e),
+            ConfigError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chanki/config.toml`, falling back on `~/.config` and
+    /// then the working directory
+    pub fn default_path() -> PathBuf {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".config"),
+                None => PathBuf::new(),
+            },
+        };
+        base.join("chanki").join("config.toml")
+    }
+
+    /// read the config at [Self::default_path]
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(Self::default_path())
+    }
+
+    /// read the config at `path`, or the defaults if it doesn't exist
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let s = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+        toml::from_str(&s).map_err(|e| ConfigError::Parse(path.to_owned(), e))
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string_pretty(self).map_err(ConfigError::Serialize)
+    }
+
+    /// write the config to `path`, creating its directory if needed
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let s = self.to_toml()?;
+        let io = |e| ConfigError::Io(path.to_owned(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io)?;
+        }
+        fs::write(path, s).map_err(io)
+    }
+
+    pub fn decks_dir(&self) -> PathBuf {
+        self.decks.clone().unwrap_or_else(Collection::default_dir)
+    }
+
+    /// an empty deck with the settings for new decks
+    pub fn new_deck(&self) -> Deck {
+        Deck {
+            scheduler: self.new_deck_scheduler.clone(),
+            limits: self.new_deck_limits,
+            ..Deck::default()
+        }
+    }
+
+    /// open the collection in [Self::decks_dir], creating new decks with
+    /// [Self::new_deck]
+    pub fn open_decks(&self) -> Result<Collection, CollectionError> {
+        Collection::open_with(self.decks_dir(), self.new_deck())
+    }
+}
@@ -1,15 +1,138 @@
-use std::{error::Error, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use chrono::{Duration, Utc};
+pub mod filter;
+pub mod simulate;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{board::Board, pgn::Pgn, run_convert, run_latex};
+use self::filter::Filter;
+use crate::{
+    annotation::{self, Brush, Shape},
+    backup,
+    board::{Board, Color, Coord, FenError},
+    cache::RenderCache,
+    clock::{Clock, SystemClock},
+    pgn::Pgn,
+    render::{RenderError, Renderer},
+    scheduler::{Scheduler, SchedulerKind, Sm2},
+    theme::Theme,
+};
+
+/// the position shown on the front of a [Card], from which its diagram is
+/// rendered on demand
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Position {
+    /// FEN of the position
+    pub fen: String,
+
+    /// the (from, to) squares of the move leading to the position
+    pub last_move: (Coord, Coord),
+
+    /// the PGN the position was taken from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pgn: Option<String>,
+
+    /// the halfmove in `pgn` at which the position occurs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub halfmove: Option<usize>,
+
+    /// the side to draw at the bottom of the diagram. if `None`, this is the
+    /// side to move
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Color>,
+
+    /// extra arrows and square markings to draw on the diagram
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shapes: Vec<Shape>,
+}
+
+impl Position {
+    /// reconstruct the [Board] described by `self.fen`
+    pub fn board(&self) -> Result<Board, FenError> {
+        Board::from_str(&self.fen)
+    }
+
+    /// render `self` as text for a terminal. see [Board::to_term]
+    pub fn to_term(&self, ansi: bool) -> Result<String, FenError> {
+        let board = self.board()?;
+        Ok(board.to_term(Some(self.last_move), self.flip(&board), ansi))
+    }
+
+    /// all of the shapes to draw for `self`: an arrow for the last move,
+    /// followed by `self.shapes`
+    pub fn all_shapes(&self) -> Vec<Shape> {
+        let (from, to) = self.last_move;
+        let mut shapes = vec![Shape::Arrow {
+            from,
+            to,
+            brush: Brush::Green,
+        }];
+        shapes.extend_from_slice(&self.shapes);
+        shapes
+    }
+
+    /// whether `board`, the [Board] for `self`, should be drawn from Black's
+    /// perspective
+    pub fn flip(&self, board: &Board) -> bool {
+        self.orientation.unwrap_or(board.to_move) == Color::Black
+    }
+}
+
+/// a stable identifier for a [Card] in its [Deck], assigned by [Deck::push]
+/// and never reused, even after the card is removed
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+#[serde(transparent)]
+pub struct CardId(pub u64);
+
+impl Display for CardId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Card {
-    /// path to the generated PGN PNG (for now). eventually it will probably
-    /// be an FEN that I'll render directly
-    pub pgn: String,
+    /// the card's identity in its deck. decks saved before cards had ids
+    /// are given their indices when loaded
+    #[serde(default)]
+    pub id: CardId,
+
+    /// when the card was created. `None` for cards from decks saved before
+    /// this was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+
+    /// the position to render on the front of the card. this is only `None`
+    /// for cards created before positions were stored, which have an `image`
+    /// instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+
+    /// path to a pre-rendered diagram PNG. older decks stored this in the
+    /// `pgn` field, hence the alias
+    #[serde(default, alias = "pgn", skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
 
     /// solution input by user and displayed directly as given
     pub answer: String,
@@ -22,6 +145,93 @@ pub struct Card {
 
     /// easiness factor for the card
     pub e_factor: f64,
+
+    /// the number of days until the chance of recalling the card falls to
+    /// 90%, as tracked by [crate::scheduler::Fsrs]. `None` until the card is
+    /// first reviewed with FSRS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stability: Option<f64>,
+
+    /// how hard the card is to remember, from 1 to 10, as tracked by FSRS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<f64>,
+
+    /// when the card was last reviewed with FSRS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_review: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub phase: Phase,
+
+    /// the day on which the card scored below 4 in a review, if it hasn't
+    /// scored at least 4 since. as in SM-2, such cards are repeated until
+    /// they do, without affecting their schedule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub again_today: Option<NaiveDate>,
+
+    /// whether the card is left out of reviews until it is unsuspended
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub suspended: bool,
+
+    /// the day on which a buried card returns to reviews
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buried_until: Option<NaiveDate>,
+
+    /// how many times the card has been forgotten after it was learned
+    #[serde(default)]
+    pub lapses: usize,
+
+    /// free-form labels for the card, such as [LEECH]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// whether `card` is new, given whether it has been `reviewed` according to
+/// [Deck::reviews]: it must also be on its first learning step or never have
+/// been repeated successfully, since cards reviewed before the log was kept
+/// have no entries in it
+fn is_new(card: &Card, reviewed: bool) -> bool {
+    !reviewed && (card.phase == Phase::Learning(0) || card.repetition == 0)
+}
+
+/// the tag given to cards that have lapsed [Deck::leech_threshold] times
+pub const LEECH: &str = "leech";
+
+/// tags for a card showing `board` from `pgn`: the game's ECO code and
+/// opening, like `ECO:C21` and `Opening:King's_Pawn_Game`, and the side to
+/// move, `color:white` or `color:black`. tags can't contain spaces, so those
+/// in the opening's name become underscores
+fn auto_tags(pgn: &Pgn, board: &Board) -> Vec<String> {
+    let mut tags = Vec::new();
+    for name in ["ECO", "Opening"] {
+        if let Some(value) = pgn.tag(name)
+            && !value.is_empty()
+            && value != "?"
+        {
+            let value: Vec<_> = value.split_whitespace().collect();
+            tags.push(format!("{name}:{}", value.join("_")));
+        }
+    }
+    tags.push(match board.to_move {
+        Color::White => "color:white".to_owned(),
+        Color::Black => "color:black".to_owned(),
+    });
+    tags
+}
+
+/// the stage of learning that a card is at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Phase {
+    /// a new card, working through [Deck::learning_steps]. holds the index
+    /// of the current step
+    Learning(usize),
+
+    /// a card scheduled by the deck's [Scheduler]
+    #[default]
+    Review,
+
+    /// a forgotten card, working through [Deck::relearning_steps]
+    Relearning(usize),
 }
 
 /// the quality of a response in SM-2.
@@ -42,105 +252,682 @@ pub enum Quality {
     Five = 5,
 }
 
+impl TryFrom<u8> for Quality {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Quality::*;
+        Ok(match value {
+            0 => Zero,
+            1 => One,
+            2 => Two,
+            3 => Three,
+            4 => Four,
+            5 => Five,
+            v => return Err(format!("quality must be 0-5, got {v}")),
+        })
+    }
+}
+
 impl Card {
+    /// build a card showing the position after `move_number` halfmoves of
+    /// `pgn`. returns [RenderError::Position] if there is no such move to
+    /// show
     pub fn new(
         pgn: &Pgn,
         move_number: usize,
-        output: String,
         answer: String,
-    ) -> Self {
+    ) -> Result<Self, RenderError> {
+        Self::new_at(pgn, move_number, answer, SystemClock.now())
+    }
+
+    /// [Self::new] for a card created, and first due, at `now`
+    pub fn new_at(
+        pgn: &Pgn,
+        move_number: usize,
+        answer: String,
+        now: DateTime<Utc>,
+    ) -> Result<Self, RenderError> {
         let mut board = Board::new();
         let moves = board.play(pgn, move_number);
+        if moves.len() != move_number || move_number == 0 {
+            return Err(RenderError::Position(format!(
+                "move {move_number} is not in the game, which has {} halfmoves",
+                moves.len()
+            )));
+        }
 
-        let dir = std::env::temp_dir().join("chanki");
-        // create_dir_all is okay with it already existing
-        std::fs::create_dir_all(&dir).unwrap();
-        std::fs::write(
-            dir.join("test.tex"),
-            board.to_latex(*moves.iter().last().unwrap()),
-        )
-        .unwrap();
-        run_latex(dir.to_str().unwrap());
-        run_convert(dir.join("test.pdf").to_str().unwrap(), &output);
-
-        Self {
-            pgn: output,
+        let tags = auto_tags(pgn, &board);
+        Ok(Self {
+            id: CardId::default(),
+            created: Some(now),
+            position: Some(Position {
+                fen: board.to_string(),
+                last_move: *moves.iter().last().unwrap(),
+                pgn: Some(pgn.to_string()),
+                halfmove: Some(move_number),
+                orientation: None,
+                shapes: pgn
+                    .comments
+                    .get(&move_number)
+                    .map(|c| annotation::parse_comment(c))
+                    .unwrap_or_default(),
+            }),
+            image: None,
             answer,
-            next_due: Utc::now(),
+            next_due: now,
             repetition: 0,
             e_factor: 2.5,
+            stability: None,
+            difficulty: None,
+            last_review: None,
+            phase: Phase::Learning(0),
+            again_today: None,
+            suspended: false,
+            buried_until: None,
+            lapses: 0,
+            tags,
+        })
+    }
+
+    /// render the diagram for `self` to PNG bytes with `renderer` and
+    /// `theme`, or load it from `self.image` for cards without a stored
+    /// position
+    pub fn render(
+        &self,
+        renderer: Renderer,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        match (&self.position, &self.image) {
+            (Some(pos), _) => {
+                let board = pos.board()?;
+                let flip = pos.flip(&board);
+                renderer.png(&board, &pos.all_shapes(), flip, theme, size)
+            }
+            (None, Some(image)) => Ok(std::fs::read(image)?),
+            (None, None) => Err(RenderError::Empty),
         }
     }
 
+    /// like [Self::render], but reusing a previous rendering of the same
+    /// position in the same style from `cache` when there is one. pieces
+    /// from a directory are only reused until their files change
+    pub fn render_cached(
+        &self,
+        cache: &RenderCache,
+        renderer: Renderer,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        let Some(pos) = &self.position else {
+            return self.render(renderer, theme, size);
+        };
+        let board = pos.board()?;
+        let key = serde_json::to_string(&(
+            &pos.fen,
+            pos.last_move,
+            pos.flip(&board),
+            &pos.shapes,
+            theme,
+            theme.pieces.files_hash(),
+            format!("{renderer:?}"),
+            size,
+        ))
+        .unwrap();
+        cache.get_or_render(&key, || self.render(renderer, theme, size))
+    }
+
+    /// update `self` with SM-2. prefer [Deck::answer], which uses the deck's
+    /// scheduler
     pub fn update_card(&mut self, q: Quality) {
-        // failed the card, restart repetitions from the beginning without
-        // touching e_factor
-        if (q as usize) < 3 {
-            self.repetition = 0;
-            self.next_due = Utc::now();
-            return;
-        }
-        // this is the least clear part to me, but it says "After each
-        // repetition session of a given day repeat again all items that scored
-        // below four in the quality assessment. Continue the repetitions until
-        // all of these items score at least four." so do you increment
-        // repetition? I think not since they are measured in days
-        if (q as usize) < 4 {
-            self.next_due = Utc::now();
-        }
-        self.repetition += 1;
-        self.update_e_factor(q);
-        self.update_interval();
+        Sm2.update(self, q, SystemClock.now());
+    }
+
+    /// whether the card is due now, taking days to start at midnight UTC.
+    /// prefer [Deck::is_due], which uses the deck's clock and
+    /// [Limits::day_start]
+    pub fn is_due(&self) -> bool {
+        let now = SystemClock.now();
+        self.is_due_at(now, now.date_naive())
+    }
+
+    /// whether the card is active on `today` and due by `now`
+    pub fn is_due_at(&self, now: DateTime<Utc>, today: NaiveDate) -> bool {
+        self.is_active_on(today) && now > self.next_due
     }
 
-    fn update_e_factor(&mut self, q: Quality) {
-        let a = 5.0 - q as usize as f64;
-        self.e_factor += 0.1 - a * (0.08 + a * 0.02);
-        if self.e_factor < 1.3 {
-            self.e_factor = 1.3;
+    /// add `tag` to the card if it doesn't have it already
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(tag.to_owned());
         }
     }
 
-    pub fn is_due(&self) -> bool {
-        Utc::now() > self.next_due
+    pub fn is_leech(&self) -> bool {
+        self.tags.iter().any(|t| t == LEECH)
+    }
+
+    /// whether the card is neither suspended nor buried on `today`
+    pub fn is_active_on(&self, today: NaiveDate) -> bool {
+        !self.suspended && self.buried_until.is_none_or(|day| today >= day)
     }
+}
+
+/// how many minutes ahead to look for cards in learning when nothing else is
+/// due, like Anki's learn ahead limit
+pub const LEARN_AHEAD: i64 = 20;
 
-    fn update_interval(&mut self) {
-        let days = Self::interval(self.repetition, self.e_factor);
-        self.next_due = Utc::now() + Duration::days(days);
+/// parse comma-separated learning steps like `1m,10m,1d` into minutes. the
+/// units are m for minutes, h for hours, and d for days
+pub fn parse_steps(s: &str) -> Option<Vec<i64>> {
+    if s.trim().is_empty() {
+        return Some(Vec::new());
     }
+    s.split(',')
+        .map(|step| {
+            let step = step.trim();
+            let unit = match step.chars().last()? {
+                'm' => 1,
+                'h' => 60,
+                'd' => 24 * 60,
+                _ => return None,
+            };
+            let n: i64 = step[..step.len() - 1].parse().ok()?;
+            (n > 0).then_some(n * unit)
+        })
+        .collect()
+}
+
+/// format minutes as in [parse_steps]
+pub fn format_steps(steps: &[i64]) -> String {
+    let steps: Vec<_> = steps
+        .iter()
+        .map(|&m| match m {
+            m if m % (24 * 60) == 0 => format!("{}d", m / (24 * 60)),
+            m if m % 60 == 0 => format!("{}h", m / 60),
+            m => format!("{m}m"),
+        })
+        .collect();
+    steps.join(",")
+}
+
+fn default_learning_steps() -> Vec<i64> {
+    vec![1, 10, 24 * 60]
+}
+
+fn default_relearning_steps() -> Vec<i64> {
+    vec![10]
+}
 
-    fn interval(n: usize, ef: f64) -> i64 {
-        match n {
-            1 => 1,
-            2 => 6,
-            n if n > 2 => (Self::interval(n - 1, ef) as f64 * ef).ceil() as i64,
-            // for usize n, this only covers 0, but the compiler doesn't know
-            // that exactly
-            _ => panic!("unknown n = {n} for interval"),
+fn default_leech_threshold() -> usize {
+    8
+}
+
+fn default_scheduled() -> bool {
+    true
+}
+
+/// whether a card with `lapses` lapses has just become a leech. as in Anki,
+/// this happens at `threshold` lapses and again every half `threshold` after
+/// that, in case a card was unsuspended without being reworked. a threshold of
+/// 0 turns leech detection off
+fn is_leech_lapse(lapses: usize, threshold: usize) -> bool {
+    threshold > 0
+        && lapses >= threshold
+        && (lapses - threshold).is_multiple_of((threshold / 2).max(1))
+}
+
+/// caps on how many cards are shown for review each day. cards in learning
+/// or relearning are never held back, so that they can finish their steps
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// the most cards to see for the first time
+    pub new_per_day: usize,
+
+    /// the most cards in [Phase::Review] to review
+    pub reviews_per_day: usize,
+
+    /// the hour, in UTC, at which each day starts, both for the limits and
+    /// for cards to repeat or bury until the next day. a later hour keeps
+    /// reviews after midnight in the day before
+    pub day_start: u32,
+}
+
+impl Limits {
+    /// limits that never hold back any cards
+    pub const NONE: Limits = Limits {
+        new_per_day: usize::MAX,
+        reviews_per_day: usize::MAX,
+        day_start: 0,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            new_per_day: 20,
+            reviews_per_day: 200,
+            day_start: 0,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// one answer to a card, as recorded in [Deck::reviews]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Review {
+    /// the card that was answered. older decks stored its index in
+    /// [Deck::cards], which is the same as its id after loading
+    pub card: CardId,
+
+    /// when the answer was given
+    pub time: DateTime<Utc>,
+
+    /// the quality of the answer, from 0 to 5
+    pub quality: u8,
+
+    /// the interval the card was scheduled with after its previous review,
+    /// in days. 0 for its first review
+    pub prev_interval: f64,
+
+    /// the time from this review until the card is next due, in days
+    pub interval: f64,
+
+    /// the card's easiness factor after the review
+    pub ease: f64,
+
+    /// how long the answer took, in seconds
+    pub time_taken: f64,
+
+    /// whether the card was new when it was answered. false for reviews
+    /// logged before this was recorded
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub new: bool,
+
+    /// whether the answer was given to the deck's scheduler, rather than
+    /// being a learning or relearning step or a repeat of a card that scored
+    /// below 4 earlier the same day. true for reviews logged before this was
+    /// recorded
+    #[serde(default = "default_scheduled")]
+    pub scheduled: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Deck {
     pub cards: Vec<Card>,
+
+    /// the algorithm used to schedule the deck's cards
+    #[serde(default)]
+    pub scheduler: SchedulerKind,
+
+    /// every answer given to the deck's cards, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reviews: Vec<Review>,
+
+    /// the delays in minutes before each repetition of a new card. a card
+    /// answered with 4 moves to the next step, and with 5 skips the rest.
+    /// after the last step, the card is handed to the scheduler
+    #[serde(default = "default_learning_steps")]
+    pub learning_steps: Vec<i64>,
+
+    /// the steps for cards answered below 3 in review, before they return to
+    /// the scheduler
+    #[serde(default = "default_relearning_steps")]
+    pub relearning_steps: Vec<i64>,
+
+    /// the number of lapses after which a card is tagged as a [LEECH]. 0
+    /// never tags cards
+    #[serde(default = "default_leech_threshold")]
+    pub leech_threshold: usize,
+
+    /// whether to also suspend cards when they become leeches
+    #[serde(default)]
+    pub leech_suspend: bool,
+
+    /// how many cards [Self::queue] gives each day
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// the source of the current time for scheduling. this is the system
+    /// clock except in tests and simulations
+    #[serde(skip, default = "system_clock")]
+    pub clock: Arc<dyn Clock>,
+
+    /// the id to give the next card pushed. 0 in decks saved before cards
+    /// had ids
+    #[serde(default)]
+    pub(crate) next_id: u64,
+}
+
+fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+impl Default for Deck {
+    fn default() -> Self {
+        Self {
+            cards: Vec::new(),
+            scheduler: SchedulerKind::default(),
+            reviews: Vec::new(),
+            learning_steps: default_learning_steps(),
+            relearning_steps: default_relearning_steps(),
+            leech_threshold: default_leech_threshold(),
+            leech_suspend: false,
+            limits: Limits::default(),
+            clock: system_clock(),
+            next_id: 0,
+        }
+    }
 }
 
 impl Deck {
     /// load a [Deck] from `path`
     pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
         let f = std::fs::File::open(path)?;
-        Ok(serde_json::from_reader(f)?)
+        let mut deck: Self = serde_json::from_reader(f)?;
+        if deck.next_id == 0 && !deck.cards.is_empty() {
+            // number the cards of an older deck by their indices, which its
+            // review log already refers to them by
+            for (i, card) in deck.cards.iter_mut().enumerate() {
+                card.id = CardId(i as u64);
+            }
+            deck.next_id = deck.cards.len() as u64;
+        }
+        Ok(deck)
     }
 
-    /// dump `self` to `path` in JSON format
+    /// dump `self` to `path` in JSON format, replacing the file atomically
+    /// with [backup::write_atomic] after making a [backup::back_up] of it
     pub fn dump(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
-        let mut f = std::fs::File::create(path)?;
-        Ok(serde_json::to_writer(&mut f, &self)?)
+        let path = path.as_ref();
+        let json = serde_json::to_vec(self)?;
+        backup::back_up(path, self.clock.now(), false)?;
+        Ok(backup::write_atomic(path, &json)?)
+    }
+
+    /// add `card` to the deck with a new id, which is returned
+    pub fn push(&mut self, mut card: Card) -> CardId {
+        let id = CardId(self.next_id);
+        self.next_id += 1;
+        card.id = id;
+        self.cards.push(card);
+        id
+    }
+
+    /// the index in [Self::cards] of the card with `id`
+    pub fn index_of(&self, id: CardId) -> Option<usize> {
+        self.cards.iter().position(|c| c.id == id)
+    }
+
+    pub fn get(&self, id: CardId) -> Option<&Card> {
+        self.cards.iter().find(|c| c.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: CardId) -> Option<&mut Card> {
+        self.cards.iter_mut().find(|c| c.id == id)
+    }
+
+    /// replace the contents of the card with `id`, its position, image, and
+    /// answer, with those of `card`, keeping its schedule and history.
+    /// returns false if there is no such card
+    pub fn update(&mut self, id: CardId, card: Card) -> bool {
+        let Some(old) = self.get_mut(id) else {
+            return false;
+        };
+        old.position = card.position;
+        old.image = card.image;
+        old.answer = card.answer;
+        true
+    }
+
+    /// remove the card with `id`, returning it. its reviews stay in
+    /// [Self::reviews], since ids aren't reused, so that they still count
+    /// toward today's limits and can be fit by [crate::optimize]
+    pub fn remove(&mut self, id: CardId) -> Option<Card> {
+        Some(self.cards.remove(self.index_of(id)?))
+    }
+
+    /// suspend or unsuspend the card with `id`. returns false if there is
+    /// no such card
+    pub fn suspend(&mut self, id: CardId, suspended: bool) -> bool {
+        let Some(card) = self.get_mut(id) else {
+            return false;
+        };
+        card.suspended = suspended;
+        true
+    }
+
+    /// hide the card with `id` from reviews until tomorrow, or show it again
+    /// if `buried` is false. returns false if there is no such card
+    pub fn bury(&mut self, id: CardId, buried: bool) -> bool {
+        let tomorrow = self.today().succ_opt();
+        let Some(card) = self.get_mut(id) else {
+            return false;
+        };
+        card.buried_until = if buried { tomorrow } else { None };
+        true
+    }
+
+    /// record an answer of quality `q` to the card with `id` that took
+    /// `time_taken`, scheduling it with `self.scheduler` and logging it in
+    /// `self.reviews`. does nothing if there is no such card
+    pub fn answer(&mut self, id: CardId, q: Quality, time_taken: Duration) {
+        let now = self.clock.now();
+        let today = self.day_of(now);
+        let scheduler = self.scheduler.scheduler();
+        let reviewed = self.reviews.iter().any(|r| r.card == id);
+        let Some(card) = self.cards.iter_mut().find(|c| c.id == id) else {
+            return;
+        };
+        let new = is_new(card, reviewed);
+        let mut scheduled = false;
+        match card.phase {
+            // an extra repetition of a card that scored below 4 earlier
+            // today, which doesn't affect its schedule
+            Phase::Review
+                if !card.is_due_at(now, today)
+                    && card.again_today == Some(today) =>
+            {
+                if (q as usize) >= 4 {
+                    card.again_today = None;
+                }
+            }
+            Phase::Review => {
+                // a card answered below 4 earlier today can be due again,
+                // since SM-2 leaves failed cards due immediately, but it only
+                // lapses once a day
+                let lapsed = card.again_today == Some(today);
+                scheduler.update(card, q, now);
+                scheduled = true;
+                card.again_today = None;
+                if (q as usize) < 3 && !lapsed {
+                    card.lapses += 1;
+                    if is_leech_lapse(card.lapses, self.leech_threshold) {
+                        card.add_tag(LEECH);
+                        if self.leech_suspend {
+                            card.suspended = true;
+                        }
+                    }
+                }
+                if (q as usize) < 3 && !self.relearning_steps.is_empty() {
+                    card.phase = Phase::Relearning(0);
+                    card.next_due = now
+                        + chrono::Duration::minutes(self.relearning_steps[0]);
+                } else if (q as usize) < 4 {
+                    card.again_today = Some(today);
+                }
+            }
+            Phase::Learning(step) | Phase::Relearning(step) => {
+                let learning = matches!(card.phase, Phase::Learning(_));
+                let steps = if learning {
+                    &self.learning_steps
+                } else {
+                    &self.relearning_steps
+                };
+                let next = match q as usize {
+                    0..=2 => Some(0),
+                    3 => Some(step),
+                    4 => Some(step + 1),
+                    _ => None,
+                };
+                match next.filter(|&n| n < steps.len()) {
+                    Some(n) => {
+                        card.phase = if learning {
+                            Phase::Learning(n)
+                        } else {
+                            Phase::Relearning(n)
+                        };
+                        card.next_due =
+                            now + chrono::Duration::minutes(steps[n]);
+                    }
+                    None => {
+                        card.phase = Phase::Review;
+                        scheduler.update(card, q, now);
+                        scheduled = true;
+                    }
+                }
+            }
+        }
+        let prev_interval = self
+            .reviews
+            .iter()
+            .rfind(|r| r.card == id)
+            .map_or(0.0, |r| r.interval);
+        self.reviews.push(Review {
+            card: id,
+            time: now,
+            quality: q as u8,
+            prev_interval,
+            interval: (card.next_due - now).num_seconds() as f64 / 86400.0,
+            ease: card.e_factor,
+            time_taken: time_taken.as_secs_f64(),
+            new,
+            scheduled,
+        });
+    }
+
+    /// the day that `time` falls on, with days starting at
+    /// [Limits::day_start]
+    pub fn day_of(&self, time: DateTime<Utc>) -> NaiveDate {
+        let start = chrono::Duration::hours(self.limits.day_start.into());
+        (time - start).date_naive()
+    }
+
+    /// the day it is now by the deck's clock
+    pub fn today(&self) -> NaiveDate {
+        self.day_of(self.clock.now())
+    }
+
+    /// whether `card` is active today
+    pub fn is_active(&self, card: &Card) -> bool {
+        card.is_active_on(self.today())
+    }
+
+    /// whether `card` is active today and due now
+    pub fn is_due(&self, card: &Card) -> bool {
+        card.is_due_at(self.clock.now(), self.today())
+    }
+
+    /// the ids of the cards to review now, in order: due cards, longest
+    /// overdue first, then those to repeat today. if there are none, cards in
+    /// learning that come due within [LEARN_AHEAD]. new cards and reviews
+    /// past today's [Self::limits] are left out
+    pub fn queue(&self) -> Vec<CardId> {
+        self.queue_of(|_| true)
+    }
+
+    /// [Self::queue] limited to the cards that match `filter`
+    pub fn queue_matching(&self, filter: &Filter) -> Vec<CardId> {
+        self.queue_of(|card| filter.matches(card))
+    }
+
+    fn queue_of(&self, keep: impl Fn(&Card) -> bool) -> Vec<CardId> {
+        let now = self.clock.now();
+        let today = self.today();
+        let cards: Vec<_> = self.cards.iter().filter(|c| keep(c)).collect();
+        let mut queue: Vec<usize> = (0..cards.len())
+            .filter(|&i| cards[i].is_due_at(now, today))
+            .collect();
+        queue.sort_by_key(|&i| cards[i].next_due);
+        queue.extend((0..cards.len()).filter(|&i| {
+            cards[i].is_active_on(today)
+                && !cards[i].is_due_at(now, today)
+                && cards[i].again_today == Some(today)
+        }));
+        let mut queue = self.limit(queue.into_iter().map(|i| cards[i].id));
+        if queue.is_empty() {
+            let ahead = now + chrono::Duration::minutes(LEARN_AHEAD);
+            let mut learning: Vec<_> = cards
+                .iter()
+                .filter(|c| {
+                    c.phase != Phase::Review && c.is_due_at(ahead, today)
+                })
+                .collect();
+            learning.sort_by_key(|c| c.next_due);
+            queue = self.limit(learning.into_iter().map(|c| c.id));
+        }
+        queue
+    }
+
+    /// the number of (new cards, other cards) studied so far today, going by
+    /// [Self::reviews]. a card counts as new if it was at its first answer
+    /// today
+    pub fn studied_today(&self) -> (usize, usize) {
+        let today = self.today();
+        let mut today_cards = HashMap::new();
+        for review in &self.reviews {
+            if self.day_of(review.time) == today {
+                today_cards.entry(review.card).or_insert(review.new);
+            }
+        }
+        let new = today_cards.values().filter(|&&new| new).count();
+        (new, today_cards.len() - new)
+    }
+
+    /// `queue` without the new cards and reviews that would go over today's
+    /// [Self::limits]
+    fn limit(&self, queue: impl Iterator<Item = CardId>) -> Vec<CardId> {
+        let limits = &self.limits;
+        let today = self.today();
+        let (mut new, mut reviews) = self.studied_today();
+        let seen: HashSet<_> = self.reviews.iter().map(|r| r.card).collect();
+        let seen_today: HashSet<_> = self
+            .reviews
+            .iter()
+            .filter(|r| self.day_of(r.time) == today)
+            .map(|r| r.card)
+            .collect();
+        queue
+            .filter(|&id| {
+                let Some(card) = self.get(id) else {
+                    return false;
+                };
+                if is_new(card, seen.contains(&id)) {
+                    new += 1;
+                    new <= limits.new_per_day
+                } else if card.phase == Phase::Review
+                    && !seen_today.contains(&id)
+                {
+                    reviews += 1;
+                    reviews <= limits.reviews_per_day
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
+    /// the cards tagged as [LEECH], most lapses first
+    pub fn leeches(&self) -> Vec<&Card> {
+        let mut leeches: Vec<_> =
+            self.cards.iter().filter(|c| c.is_leech()).collect();
+        leeches.sort_by_key(|c| std::cmp::Reverse(c.lapses));
+        leeches
     }
 
-    pub fn push(&mut self, card: Card) {
-        self.cards.push(card)
+    /// the reviews of the card with `id`, oldest first
+    pub fn reviews_of(&self, id: CardId) -> impl Iterator<Item = &Review> {
+        self.reviews.iter().filter(move |r| r.card == id)
     }
 }
@@ -0,0 +1,149 @@
+//! selecting cards by their tags, with expressions like
+//! `ECO:C2* and not endgame`
+
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use super::Card;
+
+/// a boolean expression over a card's tags. tags are matched without regard
+/// to ASCII case, and `*` in a tag matches any run of characters
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// cards with a tag matching the pattern
+    Tag(String),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+/// a tag expression that couldn't be parsed, with the reason
+#[derive(Debug)]
+pub struct FilterError(pub String);
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid filter: {}", self.0)
+    }
+}
+
+impl Error for FilterError {}
+
+impl Filter {
+    pub fn matches(&self, card: &Card) -> bool {
+        match self {
+            Filter::Tag(pattern) => card
+                .tags
+                .iter()
+                .any(|tag| glob(pattern.as_bytes(), tag.as_bytes())),
+            Filter::Not(f) => !f.matches(card),
+            Filter::And(a, b) => a.matches(card) && b.matches(card),
+            Filter::Or(a, b) => a.matches(card) || b.matches(card),
+        }
+    }
+}
+
+/// whether `text` matches `pattern`, where `*` matches any run of bytes
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        Some((p, rest)) => text.split_first().is_some_and(|(t, text)| {
+            p.eq_ignore_ascii_case(t) && glob(rest, text)
+        }),
+    }
+}
+
+/// a recursive descent parser over the tokens of an expression. `not` binds
+/// tightest, then `and`, then `or`
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.eq_ignore_ascii_case(word));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.not()?;
+        while self.keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, FilterError> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        match self.peek() {
+            Some("(") => {
+                self.pos += 1;
+                let filter = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err(FilterError("missing )".to_owned()));
+                }
+                self.pos += 1;
+                Ok(filter)
+            }
+            Some(")") => Err(FilterError("unexpected )".to_owned())),
+            Some(tok)
+                if ["and", "or"]
+                    .iter()
+                    .any(|k| tok.eq_ignore_ascii_case(k)) =>
+            {
+                Err(FilterError(format!("expected a tag before {tok}")))
+            }
+            Some(tag) => {
+                self.pos += 1;
+                Ok(Filter::Tag(tag.to_owned()))
+            }
+            None => Err(FilterError("expected a tag".to_owned())),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Vec::new();
+        for word in s.split_ascii_whitespace() {
+            // split parentheses off of the tags they touch
+            let mut rest = word;
+            while let Some(i) = rest.find(['(', ')']) {
+                if i > 0 {
+                    tokens.push(&rest[..i]);
+                }
+                tokens.push(&rest[i..i + 1]);
+                rest = &rest[i + 1..];
+            }
+            if !rest.is_empty() {
+                tokens.push(rest);
+            }
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(tok) => Err(FilterError(format!("unexpected {tok}"))),
+        }
+    }
+}
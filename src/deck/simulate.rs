@@ -0,0 +1,202 @@
+//! forecasting the review workload of a deck by simulating future reviews
+
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::Duration;
+
+use super::{Card, CardId, Deck, Limits, Phase, Quality, LEARN_AHEAD};
+use crate::clock::{Clock, FixedClock};
+
+/// the settings for [forecast]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Simulation {
+    /// how many days to simulate, starting today
+    pub days: usize,
+
+    /// the chance of remembering each card when it is reviewed
+    pub retention: f64,
+
+    /// how many new cards to add to the deck over the simulation
+    pub new_cards: usize,
+
+    /// the most of those new cards to start learning each day
+    pub new_per_day: usize,
+
+    /// the seed for the random answers, so that forecasts are repeatable
+    pub seed: u64,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            days: 30,
+            retention: 0.9,
+            new_cards: 0,
+            new_per_day: 20,
+            seed: 0,
+        }
+    }
+}
+
+/// the simulated workload for one day
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Day {
+    /// the number of different cards reviewed, including new ones
+    pub cards: usize,
+
+    /// the number of new cards introduced
+    pub new: usize,
+
+    /// the total number of answers, counting each learning step and repeat
+    pub reviews: usize,
+}
+
+/// a small deterministic random number generator (splitmix64), since the
+/// library doesn't otherwise need one
+struct Rng(u64);
+
+impl Rng {
+    /// a uniformly distributed float in [0, 1)
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// project the daily workload of `deck` under `sim`, reviewing every card
+/// with its scheduler when it comes due, regardless of the deck's limits.
+/// each answer is a 4 with probability `sim.retention` and a 1 otherwise.
+/// `deck` itself is unchanged
+pub fn forecast(deck: &Deck, sim: &Simulation) -> Vec<Day> {
+    let start = deck.clock.now();
+    let clock = FixedClock::new(start);
+    let mut deck = Deck {
+        cards: deck.cards.clone(),
+        scheduler: deck.scheduler.clone(),
+        learning_steps: deck.learning_steps.clone(),
+        relearning_steps: deck.relearning_steps.clone(),
+        leech_threshold: deck.leech_threshold,
+        leech_suspend: deck.leech_suspend,
+        limits: Limits {
+            day_start: deck.limits.day_start,
+            ..Limits::NONE
+        },
+        clock: Arc::new(clock.clone()),
+        next_id: deck.next_id,
+        ..Deck::default()
+    };
+    let mut rng = Rng(sim.seed);
+    let mut new_left = sim.new_cards;
+    let mut days = Vec::with_capacity(sim.days);
+    for d in 0..sim.days {
+        let day_start = start + Duration::days(d as i64);
+        let day_end = day_start + Duration::days(1);
+        clock.set(day_start);
+
+        let new = new_left.min(sim.new_per_day);
+        new_left -= new;
+        for _ in 0..new {
+            deck.push(new_card(&clock));
+        }
+
+        let mut day = Day {
+            new,
+            ..Day::default()
+        };
+        let mut seen = HashSet::new();
+        // bound the day in case of a very low retention
+        let limit = 100 * deck.cards.len().max(1);
+        while day.reviews < limit {
+            let Some(id) = next_card(&deck) else {
+                // wait for the next card due later today, if any
+                let now = clock.now();
+                let today = deck.today();
+                let next = deck
+                    .cards
+                    .iter()
+                    .filter(|c| c.is_active_on(today))
+                    .map(|c| c.next_due)
+                    .filter(|&due| due >= now && due < day_end)
+                    .min();
+                match next {
+                    Some(due) => {
+                        clock.set(due + Duration::seconds(1));
+                        continue;
+                    }
+                    None => break,
+                }
+            };
+            let q = if rng.next() < sim.retention {
+                Quality::Four
+            } else {
+                Quality::One
+            };
+            deck.answer(id, q, Default::default());
+            // nothing here reads the log, and letting it grow would make
+            // each answer slower than the last
+            deck.reviews.clear();
+            if seen.insert(id) {
+                day.cards += 1;
+            }
+            day.reviews += 1;
+            // answering takes a little time
+            clock.advance(Duration::seconds(10));
+        }
+        days.push(day);
+    }
+    days
+}
+
+/// the card at the front of [Deck::queue], found without building the whole
+/// queue for every answer. the forecast ignores the deck's limits, so this
+/// only needs the cards themselves
+fn next_card(deck: &Deck) -> Option<CardId> {
+    let now = deck.clock.now();
+    let today = deck.today();
+    let due = || {
+        deck.cards
+            .iter()
+            .filter(|c| c.is_due_at(now, today))
+            .min_by_key(|c| c.next_due)
+    };
+    let again = || {
+        deck.cards
+            .iter()
+            .find(|c| c.is_active_on(today) && c.again_today == Some(today))
+    };
+    let ahead = || {
+        let ahead = now + Duration::minutes(LEARN_AHEAD);
+        deck.cards
+            .iter()
+            .filter(|c| c.phase != Phase::Review && c.is_due_at(ahead, today))
+            .min_by_key(|c| c.next_due)
+    };
+    due().or_else(again).or_else(ahead).map(|c| c.id)
+}
+
+/// a blank card standing in for one not yet added to the deck
+fn new_card(clock: &FixedClock) -> Card {
+    Card {
+        id: CardId::default(),
+        created: Some(clock.now()),
+        position: None,
+        image: None,
+        answer: String::new(),
+        next_due: clock.now(),
+        repetition: 0,
+        e_factor: 2.5,
+        stability: None,
+        difficulty: None,
+        last_review: None,
+        phase: Phase::Learning(0),
+        again_today: None,
+        suspended: false,
+        buried_until: None,
+        lapses: 0,
+        tags: Vec::new(),
+    }
+}
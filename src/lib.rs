@@ -2,29 +2,74 @@
 
 use std::process::Command;
 
+use render::RenderError;
+
+pub mod animation;
+pub mod annotation;
+pub mod backup;
 pub mod board;
+pub mod cache;
+pub mod clock;
+pub mod collection;
+pub mod config;
 pub mod deck;
+pub mod optimize;
 pub mod pgn;
+pub mod png;
+pub mod render;
+pub mod scheduler;
+pub mod svg;
+pub mod term;
+pub mod theme;
 
 #[cfg(test)]
 mod tests;
 
 const DEBUG: bool = false;
 
-/// hard-coded path to single deck for now. eventually use
-/// $HOME/.config/chanki/deck.json
+/// the path of the single deck used before decks were kept in a
+/// [collection::Collection], relative to the working directory. a new
+/// collection imports it
 pub const DECK_PATH: &str = "test_deck.json";
 
-pub fn run_latex(dir: &str) {
-    Command::new("pdflatex")
-        .args(["-output-directory", dir, "test.tex"])
+/// run `program` with `args`, turning a failure to start it or an
+/// unsuccessful exit status into a [RenderError]
+fn run(program: &'static str, args: &[&str]) -> Result<(), RenderError> {
+    let output = Command::new(program)
+        .args(args)
         .output()
-        .expect("failed to compile test.tex");
+        .map_err(|error| RenderError::Spawn { program, error })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    // pdflatex reports its errors on stdout, so fall back to the end of that
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let text = if stderr.trim().is_empty() {
+        &stdout
+    } else {
+        &stderr
+    };
+    let lines: Vec<_> = text.trim_end().lines().collect();
+    Err(RenderError::Tool {
+        program,
+        status: output.status,
+        output: lines[lines.len().saturating_sub(20)..].join("\n"),
+    })
 }
 
-pub fn run_convert(pdf: &str, png: &str) {
-    Command::new("convert")
-        .args(["-density", "300", pdf, "-quality", "90", png])
-        .output()
-        .expect("failed to convert test.tex to out.png");
+pub fn run_latex(dir: &str) -> Result<(), RenderError> {
+    run(
+        "pdflatex",
+        &[
+            "-interaction=nonstopmode",
+            "-output-directory",
+            dir,
+            &format!("{dir}/test.tex"),
+        ],
+    )
+}
+
+pub fn run_convert(pdf: &str, png: &str) -> Result<(), RenderError> {
+    run("convert", &["-density", "300", pdf, "-quality", "90", png])
 }
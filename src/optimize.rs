@@ -0,0 +1,202 @@
+//! fitting the [Fsrs] weights to a deck's review history
+
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deck::{CardId, Deck, Quality, Review},
+    scheduler::Fsrs,
+};
+
+/// the range that each FSRS weight is kept within while fitting, from the
+/// reference optimizer
+const BOUNDS: [(f64, f64); 17] = [
+    (0.1, 100.0),
+    (0.1, 100.0),
+    (0.1, 100.0),
+    (0.1, 100.0),
+    (1.0, 10.0),
+    (0.1, 5.0),
+    (0.1, 5.0),
+    (0.0, 0.75),
+    (0.0, 4.5),
+    (0.0, 0.8),
+    (0.01, 3.5),
+    (0.1, 5.0),
+    (0.01, 0.2),
+    (0.01, 0.9),
+    (0.01, 3.0),
+    (0.0, 1.0),
+    (1.0, 6.0),
+];
+
+/// the most passes over the weights to make before giving up on converging
+const MAX_ROUNDS: usize = 200;
+
+/// how well a set of weights predicts the review history
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fit {
+    /// the mean log-loss of the predicted recall probabilities against
+    /// whether each card was actually recalled. lower is better
+    pub log_loss: f64,
+
+    /// the mean predicted probability of recall at each review
+    pub predicted_retention: f64,
+}
+
+/// the result of [optimize]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// the number of reviews the weights were fit to. a card's first review
+    /// predicts nothing, so it isn't counted
+    pub reviews: usize,
+
+    /// the fraction of those reviews that were recalled, with a quality of
+    /// at least 3
+    pub actual_retention: f64,
+
+    /// the fit of the starting weights
+    pub before: Fit,
+
+    /// the fit of [Self::weights]
+    pub after: Fit,
+
+    /// the optimized weights
+    pub weights: [f64; 17],
+}
+
+/// the review history doesn't contain any reviews to fit weights to
+#[derive(Debug)]
+pub struct NotEnoughReviews;
+
+impl Display for NotEnoughReviews {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for NotEnoughReviews {}
+
+/// one card's reviews as (days since the previous review, quality) pairs,
+/// oldest first
+type History = Vec<(f64, Quality)>;
+
+/// group the reviews of `deck` by card, keeping the answers that
+/// [Deck::answer] gave to the scheduler. learning steps are left out, so a
+/// card's memory starts when it graduates, as it does when scheduling. of
+/// those answers, only the first on each of the deck's days is kept: like
+/// FSRS's own optimizer, this models memory a day at a time, although the
+/// scheduler also sees a lapsed card finish relearning the same day
+fn histories(deck: &Deck) -> Vec<History> {
+    let mut by_card: HashMap<CardId, Vec<&Review>> = HashMap::new();
+    for r in deck.reviews.iter().filter(|r| r.scheduled) {
+        by_card.entry(r.card).or_default().push(r);
+    }
+    let mut cards: Vec<_> = by_card.into_iter().collect();
+    // keep the order deterministic for reproducible fits
+    cards.sort_by_key(|(card, _)| *card);
+    cards
+        .into_iter()
+        .map(|(_, mut reviews)| {
+            reviews.sort_by_key(|r| r.time);
+            let mut prev: Option<DateTime<Utc>> = None;
+            reviews
+                .into_iter()
+                .filter_map(|r| {
+                    if prev
+                        .is_some_and(|p| deck.day_of(p) == deck.day_of(r.time))
+                    {
+                        return None;
+                    }
+                    let elapsed = prev.map_or(0.0, |p| {
+                        (r.time - p).num_seconds().max(0) as f64 / 86400.0
+                    });
+                    prev = Some(r.time);
+                    Some((elapsed, Quality::try_from(r.quality).ok()?))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// replay `histories` with `fsrs`, predicting each review after the first
+fn fit(fsrs: &Fsrs, histories: &[History]) -> Fit {
+    let mut loss = 0.0;
+    let mut predicted = 0.0;
+    let mut n = 0;
+    for history in histories {
+        let mut memory = None;
+        for &(elapsed, q) in history {
+            if let Some((s, _)) = memory {
+                let r =
+                    Fsrs::retrievability(elapsed, s).clamp(1e-6, 1.0 - 1e-6);
+                let y = if (q as usize) < 3 { 0.0 } else { 1.0 };
+                loss -= y * r.ln() + (1.0 - y) * (1.0 - r).ln();
+                predicted += r;
+                n += 1;
+            }
+            memory = Some(fsrs.next_memory(memory, elapsed, q));
+        }
+    }
+    let n = n.max(1) as f64;
+    Fit {
+        log_loss: loss / n,
+        predicted_retention: predicted / n,
+    }
+}
+
+/// fit the weights of `start` to the reviews of `deck` by minimizing
+/// log-loss, using a pattern search that nudges one weight at a time and
+/// shrinks the nudges once none of them help
+pub fn optimize(start: &Fsrs, deck: &Deck) -> Result<Report, NotEnoughReviews> {
+    let histories = histories(deck);
+    let evaluated: Vec<_> =
+        histories.iter().flat_map(|h| h.iter().skip(1)).collect();
+    if evaluated.is_empty() {
+        return Err(NotEnoughReviews);
+    }
+    let recalled = evaluated.iter().filter(|(_, q)| *q as usize >= 3).count();
+
+    let before = fit(start, &histories);
+    let mut best = start.clone();
+    let mut best_fit = before;
+    let mut steps: Vec<f64> =
+        BOUNDS.iter().map(|(lo, hi)| (hi - lo) / 20.0).collect();
+    for _ in 0..MAX_ROUNDS {
+        let mut improved = false;
+        for i in 0..best.weights.len() {
+            for dir in [1.0, -1.0] {
+                let mut candidate = best.clone();
+                let (lo, hi) = BOUNDS[i];
+                candidate.weights[i] =
+                    (best.weights[i] + dir * steps[i]).clamp(lo, hi);
+                let f = fit(&candidate, &histories);
+                if f.log_loss < best_fit.log_loss - 1e-9 {
+                    best = candidate;
+                    best_fit = f;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        if !improved {
+            steps.iter_mut().for_each(|s| *s /= 2.0);
+            if steps
+                .iter()
+                .zip(BOUNDS)
+                .all(|(s, (lo, hi))| *s < (hi - lo) / 1e4)
+            {
+                break;
+            }
+        }
+    }
+
+    Ok(Report {
+        reviews: evaluated.len(),
+        actual_retention: recalled as f64 / evaluated.len() as f64,
+        before,
+        after: best_fit,
+        weights: best.weights,
+    })
+}
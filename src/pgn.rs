@@ -1,5 +1,6 @@
 use std::{
-    error::Error, fmt::Display, fs::read_to_string, path::Path, str::FromStr,
+    collections::HashMap, error::Error, fmt::Display, fs::read_to_string,
+    path::Path, str::FromStr,
 };
 
 #[derive(Debug)]
@@ -17,7 +18,14 @@ impl Move {
 
 #[derive(Debug)]
 pub struct Pgn {
+    /// the name and value of each tag pair, like `[ECO "C21"]`, in order
+    pub tags: Vec<(String, String)>,
+
     pub moves: Vec<Move>,
+
+    /// the text of the `{...}` comments in the game, keyed by the number of
+    /// halfmoves played before the comment
+    pub comments: HashMap<usize, String>,
 }
 
 #[derive(Debug)]
@@ -40,30 +48,80 @@ impl Pgn {
         let s = read_to_string(path)?;
         Ok(Self::from_str(&s)?)
     }
+
+    /// the value of the tag called `name`, if there is one
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
 }
 
 impl FromStr for Pgn {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // locate the newline between tags and game itself
-        let Some(start) = s.lines().position(str::is_empty) else {
-		    return Err(ParseError);
-	        };
-        let game: Vec<_> = s.lines().skip(start + 1).collect();
+        // locate the newline between tags and game itself. without any tags,
+        // like the movetext stored in cards, the whole string is the game
+        let start = if s.trim_start().starts_with('[') {
+            let Some(start) = s.lines().position(str::is_empty) else {
+		        return Err(ParseError);
+	            };
+            start + 1
+        } else {
+            0
+        };
+        let tags = s
+            .lines()
+            .take(start)
+            .filter_map(|line| {
+                let line = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+                let (name, value) = line.split_once(char::is_whitespace)?;
+                let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+                Some((name.to_owned(), value.to_owned()))
+            })
+            .collect();
+        let game: Vec<_> = s.lines().skip(start).collect();
         let game = game.join(" ");
+
+        // split the movetext into SAN moves and comments, dropping move
+        // numbers, NAGs, and the game result
+        let mut sans = Vec::new();
+        let mut comments: HashMap<usize, String> = HashMap::new();
+        let mut rest = game.as_str();
+        while !rest.is_empty() {
+            let (text, comment) = match rest.split_once('{') {
+                Some((text, tail)) => {
+                    let (comment, tail) =
+                        tail.split_once('}').ok_or(ParseError)?;
+                    rest = tail;
+                    (text, Some(comment))
+                }
+                None => (std::mem::take(&mut rest), None),
+            };
+            sans.extend(text.split_ascii_whitespace().filter(|tok| {
+                !tok.ends_with('.')
+                    && !tok.starts_with('$')
+                    && !["1-0", "0-1", "1/2-1/2", "*"].contains(tok)
+            }));
+            if let Some(comment) = comment {
+                let entry = comments.entry(sans.len()).or_default();
+                if !entry.is_empty() {
+                    entry.push(' ');
+                }
+                entry.push_str(comment.trim());
+            }
+        }
+
         Ok(Self {
-            moves: game
-                .split_ascii_whitespace()
-                .array_chunks::<3>()
-                .map(|[mov, white, black]| {
-                    Move::new(
-                        mov.trim_end_matches('.').parse().unwrap(),
-                        white.to_owned(),
-                        black.to_owned(),
-                    )
+            tags,
+            moves: sans
+                .into_iter()
+                .array_chunks::<2>()
+                .enumerate()
+                .map(|(i, [white, black])| {
+                    Move::new(i + 1, white.to_owned(), black.to_owned())
                 })
                 .collect(),
+            comments,
         })
     }
 }
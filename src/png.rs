@@ -0,0 +1,70 @@
+//! pure-Rust rasterization of the diagrams produced by [Board::to_svg]
+
+use std::sync::{Arc, OnceLock};
+
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{fontdb::Database, Options, Tree},
+};
+
+use crate::{
+    annotation::Shape, board::Board, render::RenderError, theme::Theme,
+};
+
+/// the system fonts, loaded once on first use since scanning them is much
+/// slower than rendering a diagram
+fn fonts() -> Arc<Database> {
+    static FONTS: OnceLock<Arc<Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+/// rasterize the SVG document `svg` into a square PNG image with sides of
+/// `size` pixels
+pub fn svg_to_png(svg: &str, size: u32) -> Result<Vec<u8>, RenderError> {
+    svg_to_pixmap(svg, size)?
+        .encode_png()
+        .map_err(|e| RenderError::Raster(e.to_string()))
+}
+
+/// rasterize `svg` as in [svg_to_png], but return the raw pixels
+pub(crate) fn svg_to_pixmap(
+    svg: &str,
+    size: u32,
+) -> Result<Pixmap, RenderError> {
+    let opt = Options {
+        fontdb: fonts(),
+        ..Options::default()
+    };
+    let tree = Tree::from_str(svg, &opt)
+        .map_err(|e| RenderError::Raster(e.to_string()))?;
+    let mut pixmap = Pixmap::new(size, size).ok_or_else(|| {
+        RenderError::Raster(format!("invalid image size {size}"))
+    })?;
+    let scale = size as f32 / tree.size().width().max(tree.size().height());
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+impl Board {
+    /// render `self` as a PNG image with sides of `size` pixels. see
+    /// [Board::to_svg] for the other arguments
+    pub fn to_png(
+        &self,
+        shapes: &[Shape],
+        flip: bool,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        svg_to_png(&self.to_svg(shapes, flip, theme), size)
+    }
+}
@@ -0,0 +1,222 @@
+//! selection between the available diagram rendering backends
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io,
+    path::PathBuf,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotation::Shape,
+    board::{Board, FenError},
+    deck::Card,
+    run_convert, run_latex,
+    theme::Theme,
+};
+
+/// the default side length of rendered diagrams, in pixels
+pub const DEFAULT_SIZE: u32 = 400;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Renderer {
+    /// rasterize [Board::to_svg] in-process. this requires no external tools
+    #[default]
+    Native,
+
+    /// compile [Board::to_latex] with pdflatex and convert the result to PNG
+    /// with ImageMagick
+    Latex,
+}
+
+/// the ways that producing a diagram can fail
+#[derive(Debug)]
+pub enum RenderError {
+    /// the position to draw is invalid, such as an unparseable FEN or a move
+    /// number outside of the game
+    Position(String),
+
+    /// reading or writing one of the files involved failed
+    Io(io::Error),
+
+    /// an external tool could not be started, usually because it is not
+    /// installed
+    Spawn {
+        program: &'static str,
+        error: io::Error,
+    },
+
+    /// an external tool exited unsuccessfully. `output` holds the end of its
+    /// error output
+    Tool {
+        program: &'static str,
+        status: ExitStatus,
+        output: String,
+    },
+
+    /// rasterizing the SVG diagram or encoding the resulting image failed
+    Raster(String),
+
+    /// the card has neither a position nor an image to show
+    Empty,
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::Position(e) => write!(f, "invalid position: {e}"),
+            RenderError::Io(e) => write!(f, "{e}"),
+            RenderError::Spawn { program, error } => {
+                write!(f, "failed to run {program}: {error}")
+            }
+            RenderError::Tool {
+                program,
+                status,
+                output,
+            } => write!(f, "{program} failed ({status}):\n{output}"),
+            RenderError::Raster(e) => write!(f, "failed to draw image: {e}"),
+            RenderError::Empty => write!(f, "card has no position or image"),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Io(e) | RenderError::Spawn { error: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> Self {
+        RenderError::Io(e)
+    }
+}
+
+impl From<FenError> for RenderError {
+    fn from(e: FenError) -> Self {
+        RenderError::Position(e.to_string())
+    }
+}
+
+impl Renderer {
+    /// every renderer, for listing in the user interfaces
+    pub const ALL: [Renderer; 2] = [Renderer::Native, Renderer::Latex];
+
+    /// look up a renderer by its lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|r| format!("{r:?}").eq_ignore_ascii_case(name))
+    }
+
+    /// render `board` in the style of `theme` with `shapes` drawn on top to
+    /// PNG bytes, from Black's perspective if `flip` is true. the LaTeX
+    /// backend ignores `size` and always renders at 300 DPI
+    pub fn png(
+        &self,
+        board: &Board,
+        shapes: &[Shape],
+        flip: bool,
+        theme: &Theme,
+        size: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        match self {
+            Renderer::Native => board.to_png(shapes, flip, theme, size),
+            Renderer::Latex => {
+                let dir = TempDir::new()?;
+                std::fs::write(
+                    dir.0.join("test.tex"),
+                    board.to_latex(shapes, flip, theme),
+                )?;
+                let pdf = dir.0.join("test.pdf");
+                let png = dir.0.join("test.png");
+                run_latex(dir.0.to_str().unwrap())?;
+                run_convert(pdf.to_str().unwrap(), png.to_str().unwrap())?;
+                Ok(std::fs::read(png)?)
+            }
+        }
+    }
+}
+
+/// a uniquely named directory under `$TMPDIR/chanki`, removed with its
+/// contents when dropped. each LaTeX render gets its own so that concurrent
+/// renders, in this process or another, don't overwrite each other's files
+pub(crate) struct TempDir(pub(crate) PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let parent = std::env::temp_dir().join("chanki");
+        std::fs::create_dir_all(&parent)?;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos();
+            let dir = parent.join(format!(
+                "{}-{}-{nanos}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed),
+            ));
+            // unlike create_dir_all, this fails if another render got there
+            // first
+            match std::fs::create_dir(&dir) {
+                Ok(()) => return Ok(Self(dir)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// render each of `cards` as by [Card::render], spreading the work over all
+/// available cores. the results are in the same order as `cards`
+pub fn render_all(
+    cards: &[Card],
+    renderer: Renderer,
+    theme: &Theme,
+    size: u32,
+) -> Vec<Result<Vec<u8>, RenderError>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(cards.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<_>> =
+        Mutex::new((0..cards.len()).map(|_| None).collect());
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(card) = cards.get(i) else {
+                    break;
+                };
+                let png = card.render(renderer, theme, size);
+                results.lock().unwrap()[i] = Some(png);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every card is rendered"))
+        .collect()
+}
@@ -0,0 +1,221 @@
+//! spaced repetition algorithms for deciding when each card is next due
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::deck::{Card, Quality};
+
+pub trait Scheduler {
+    /// update the scheduling state of `card`, including `card.next_due`,
+    /// after it was answered with quality `q` at `now`
+    fn update(&self, card: &mut Card, q: Quality, now: DateTime<Utc>);
+}
+
+/// the SuperMemo 2 algorithm, as described at
+/// <https://super-memory.com/english/ol/sm2.htm>
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sm2;
+
+impl Scheduler for Sm2 {
+    fn update(&self, card: &mut Card, q: Quality, now: DateTime<Utc>) {
+        // failed the card, restart repetitions from the beginning without
+        // touching e_factor
+        if (q as usize) < 3 {
+            card.repetition = 0;
+            card.next_due = now;
+            return;
+        }
+        // "After each repetition session of a given day repeat again all items
+        // that scored below four in the quality assessment. Continue the
+        // repetitions until all of these items score at least four." those
+        // extra repetitions don't change the schedule, so they are handled by
+        // [crate::deck::Deck::queue] rather than here
+        card.repetition += 1;
+        Self::update_e_factor(card, q);
+        let days = Self::interval(card.repetition, card.e_factor);
+        card.next_due = now + Duration::days(days);
+    }
+}
+
+impl Sm2 {
+    fn update_e_factor(card: &mut Card, q: Quality) {
+        let a = 5.0 - q as usize as f64;
+        card.e_factor += 0.1 - a * (0.08 + a * 0.02);
+        if card.e_factor < 1.3 {
+            card.e_factor = 1.3;
+        }
+    }
+
+    /// the interval in days after the `n`th successful repetition with
+    /// easiness factor `ef`
+    pub fn interval(n: usize, ef: f64) -> i64 {
+        match n {
+            1 => 1,
+            2 => 6,
+            n if n > 2 => (Self::interval(n - 1, ef) as f64 * ef).ceil() as i64,
+            // for usize n, this only covers 0, but the compiler doesn't know
+            // that exactly
+            _ => panic!("unknown n = {n} for interval"),
+        }
+    }
+}
+
+/// the default FSRS-4.5 weights, fit by the FSRS authors to a large
+/// collection of Anki reviews
+pub const DEFAULT_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474,
+    0.1367, 1.0461, 2.1072, 0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+];
+
+/// the exponent of the FSRS forgetting curve
+const DECAY: f64 = -0.5;
+
+/// chosen so that retrievability is 90% when the elapsed time equals the
+/// stability
+const FACTOR: f64 = 19.0 / 81.0;
+
+/// version 4.5 of the Free Spaced Repetition Scheduler, the algorithm used
+/// by Anki. each card's memory is modeled by its stability, the number of
+/// days until recall probability falls to 90%, and its difficulty, from 1
+/// to 10. see <https://github.com/open-spaced-repetition/fsrs4anki/wiki>
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fsrs {
+    pub weights: [f64; 17],
+
+    /// the probability of recalling a card that the intervals aim for.
+    /// higher values mean more frequent reviews
+    pub desired_retention: f64,
+
+    /// the longest interval to schedule, in days
+    pub maximum_interval: f64,
+}
+
+impl Default for Fsrs {
+    fn default() -> Self {
+        Self {
+            weights: DEFAULT_WEIGHTS,
+            desired_retention: 0.9,
+            maximum_interval: 36500.0,
+        }
+    }
+}
+
+/// the four FSRS answer buttons, Again through Easy, as 1 through 4
+fn grade(q: Quality) -> f64 {
+    match q {
+        Quality::Zero | Quality::One | Quality::Two => 1.0,
+        Quality::Three => 2.0,
+        Quality::Four => 3.0,
+        Quality::Five => 4.0,
+    }
+}
+
+impl Fsrs {
+    /// the probability of recalling a card with stability `s` after `t` days
+    pub fn retrievability(t: f64, s: f64) -> f64 {
+        (1.0 + FACTOR * t / s).powf(DECAY)
+    }
+
+    /// the number of days until recall probability falls to
+    /// `self.desired_retention` for a card with stability `s`
+    pub fn interval(&self, s: f64) -> f64 {
+        let days =
+            s / FACTOR * (self.desired_retention.powf(1.0 / DECAY) - 1.0);
+        days.round().clamp(1.0, self.maximum_interval)
+    }
+
+    fn initial_stability(&self, g: f64) -> f64 {
+        self.weights[g as usize - 1].max(0.1)
+    }
+
+    fn initial_difficulty(&self, g: f64) -> f64 {
+        let w = &self.weights;
+        (w[4] - (g - 3.0) * w[5]).clamp(1.0, 10.0)
+    }
+
+    fn next_difficulty(&self, d: f64, g: f64) -> f64 {
+        let w = &self.weights;
+        let d = d - w[6] * (g - 3.0);
+        // mean reversion towards the difficulty of a new card answered Good
+        (w[7] * self.initial_difficulty(3.0) + (1.0 - w[7]) * d)
+            .clamp(1.0, 10.0)
+    }
+
+    fn next_stability(&self, d: f64, s: f64, r: f64, g: f64) -> f64 {
+        let w = &self.weights;
+        if g == 1.0 {
+            return w[11]
+                * d.powf(-w[12])
+                * ((s + 1.0).powf(w[13]) - 1.0)
+                * (w[14] * (1.0 - r)).exp();
+        }
+        let hard_penalty = if g == 2.0 { w[15] } else { 1.0 };
+        let easy_bonus = if g == 4.0 { w[16] } else { 1.0 };
+        s * (w[8].exp()
+            * (11.0 - d)
+            * s.powf(-w[9])
+            * ((w[10] * (1.0 - r)).exp() - 1.0)
+            * hard_penalty
+            * easy_bonus
+            + 1.0)
+    }
+
+    /// the (stability, difficulty) of a card after an answer of quality `q`,
+    /// given its previous memory state and the days elapsed since its last
+    /// review. `memory` is `None` for a card's first review
+    pub fn next_memory(
+        &self,
+        memory: Option<(f64, f64)>,
+        elapsed: f64,
+        q: Quality,
+    ) -> (f64, f64) {
+        let g = grade(q);
+        match memory {
+            None => (self.initial_stability(g), self.initial_difficulty(g)),
+            Some((s, d)) => {
+                let r = Self::retrievability(elapsed, s);
+                (self.next_stability(d, s, r, g), self.next_difficulty(d, g))
+            }
+        }
+    }
+}
+
+impl Scheduler for Fsrs {
+    fn update(&self, card: &mut Card, q: Quality, now: DateTime<Utc>) {
+        let memory = card.stability.zip(card.difficulty);
+        let elapsed = card.last_review.map_or(0.0, |last| {
+            (now - last).num_seconds().max(0) as f64 / 86400.0
+        });
+        let (s, d) = self.next_memory(memory, elapsed, q);
+        card.stability = Some(s);
+        card.difficulty = Some(d);
+        card.last_review = Some(now);
+        // like SM-2, a forgotten card is repeated the same day
+        if (q as usize) < 3 {
+            card.repetition = 0;
+            card.next_due = now;
+            return;
+        }
+        card.repetition += 1;
+        let days = self.interval(s);
+        card.next_due = now + Duration::seconds((days * 86400.0) as i64);
+    }
+}
+
+/// the choice of [Scheduler] for a deck, along with its settings
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum SchedulerKind {
+    #[default]
+    Sm2,
+    Fsrs(Fsrs),
+}
+
+impl SchedulerKind {
+    pub fn scheduler(&self) -> &dyn Scheduler {
+        match self {
+            SchedulerKind::Sm2 => &Sm2,
+            SchedulerKind::Fsrs(fsrs) => fsrs,
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! native SVG rendering of a [Board], so that diagrams can be generated
+//! without pdflatex or ImageMagick
+
+use std::fmt::Write;
+
+use crate::{
+    annotation::{Brush, Shape},
+    board::{to_idx, Board, Color, Coord, Piece, PieceType},
+    theme::{piece_file, PieceSet, Theme},
+};
+
+/// side length of a single square. the piece paths below are drawn on a grid
+/// of this size
+const SQUARE: f64 = 45.0;
+
+/// width of the margin holding the rank and file labels
+const MARGIN: f64 = 20.0;
+
+/// width of the frame drawn around the board when [Theme::border] is set
+const BORDER: f64 = 6.0;
+
+/// where things go in the diagram for a given [Theme] and orientation
+struct Layout {
+    flip: bool,
+    /// space to the left of and below the board for coordinates
+    margin: f64,
+    /// space around the board for the border
+    border: f64,
+}
+
+impl Layout {
+    fn new(theme: &Theme, flip: bool) -> Self {
+        Self {
+            flip,
+            margin: if theme.coordinates { MARGIN } else { 0.0 },
+            border: if theme.border { BORDER } else { 0.0 },
+        }
+    }
+
+    /// the side length of the whole diagram
+    fn size(&self) -> f64 {
+        8.0 * SQUARE + 2.0 * self.border + self.margin
+    }
+
+    /// the top left corner of the square at `(file, rank)`, with the board
+    /// rotated 180 degrees if `self.flip` is true
+    fn corner(&self, (file, rank): Coord) -> (f64, f64) {
+        let (col, row) = if self.flip {
+            (7 - to_idx(file), rank)
+        } else {
+            (to_idx(file), 7 - rank)
+        };
+        (
+            self.margin + self.border + col as f64 * SQUARE,
+            self.border + row as f64 * SQUARE,
+        )
+    }
+
+    /// the center of the square at `coord`
+    fn center(&self, coord: Coord) -> (f64, f64) {
+        let (x, y) = self.corner(coord);
+        (x + SQUARE / 2.0, y + SQUARE / 2.0)
+    }
+}
+
+impl Board {
+    /// render `self` as a standalone SVG document in the style of `theme`,
+    /// with `shapes` drawn on top. if `flip` is true, the board is drawn from
+    /// Black's perspective
+    pub fn to_svg(
+        &self,
+        shapes: &[Shape],
+        flip: bool,
+        theme: &Theme,
+    ) -> String {
+        let layout = Layout::new(theme, flip);
+        let size = layout.size();
+        let mut s = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{size}" height="{size}" viewBox="0 0 {size} {size}">
+<rect width="{size}" height="{size}" fill="white"/>
+"#
+        );
+        if theme.border {
+            let x = layout.margin + BORDER / 2.0;
+            let y = BORDER / 2.0;
+            let side = 8.0 * SQUARE + BORDER;
+            writeln!(
+                s,
+                r##"<rect x="{x}" y="{y}" width="{side}" height="{side}" fill="none" stroke="#404040" stroke-width="{BORDER}"/>"##
+            )
+            .unwrap();
+        }
+        let (light, dark) = theme.board.colors();
+        for rank in 0..8 {
+            for file in 'a'..='h' {
+                let (x, y) = layout.corner((file, rank));
+                let fill = if (to_idx(file) + rank) % 2 == 1 {
+                    light
+                } else {
+                    dark
+                };
+                writeln!(
+                    s,
+                    r#"<rect x="{x}" y="{y}" width="{SQUARE}" height="{SQUARE}" fill="{fill}"/>"#
+                )
+                .unwrap();
+            }
+        }
+        for shape in shapes {
+            if let Shape::Highlight { square, brush } = shape {
+                let (x, y) = layout.corner(*square);
+                let color = brush.hex();
+                writeln!(
+                    s,
+                    r#"<rect x="{x}" y="{y}" width="{SQUARE}" height="{SQUARE}" fill="{color}" opacity="0.5"/>"#
+                )
+                .unwrap();
+            }
+        }
+        if theme.coordinates {
+            write_coordinates(&mut s, &layout);
+        }
+        for rank in 0..8 {
+            for file in 'a'..='h' {
+                if let Some(piece) = self[(file, rank)] {
+                    let (x, y) = layout.corner((file, rank));
+                    match &theme.pieces {
+                        PieceSet::Builtin => write_piece(&mut s, piece, x, y),
+                        PieceSet::Cburnett => {
+                            let svg = theme
+                                .pieces
+                                .bundled(piece.color, piece.typ)
+                                .unwrap();
+                            embed_svg(&mut s, svg.as_bytes(), x, y)
+                        }
+                        PieceSet::Directory(dir) => {
+                            let path =
+                                dir.join(piece_file(piece.color, piece.typ));
+                            match std::fs::read(path) {
+                                Ok(svg) => embed_svg(&mut s, &svg, x, y),
+                                // fall back on the builtin set rather than
+                                // dropping the piece entirely
+                                Err(_) => write_piece(&mut s, piece, x, y),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for shape in shapes {
+            match *shape {
+                Shape::Arrow { from, to, brush } => {
+                    write_arrow(&mut s, &layout, from, to, brush)
+                }
+                Shape::Circle { square, brush } => {
+                    let (cx, cy) = layout.center(square);
+                    let color = brush.hex();
+                    let width = 0.08 * SQUARE;
+                    let r = SQUARE / 2.0 - width;
+                    writeln!(
+                        s,
+                        r#"<circle cx="{cx}" cy="{cy}" r="{r}" fill="none" stroke="{color}" stroke-width="{width}" opacity="0.8"/>"#
+                    )
+                    .unwrap();
+                }
+                Shape::Highlight { .. } => {}
+            }
+        }
+        s.push_str("</svg>\n");
+        s
+    }
+}
+
+/// label the files along the bottom and the ranks along the left side of the
+/// board
+fn write_coordinates(s: &mut String, layout: &Layout) {
+    // resvg doesn't fall back from the generic family names, so list some
+    // common concrete fonts first
+    const STYLE: &str = r#"font-family="DejaVu Sans, Liberation Sans, Arial, Helvetica, sans-serif" font-size="14" fill="black" text-anchor="middle""#;
+    for file in 'a'..='h' {
+        let (x, _) = layout.center((file, 0));
+        let y = layout.size() - 5.0;
+        writeln!(s, r#"<text x="{x}" y="{y}" {STYLE}>{file}</text>"#).unwrap();
+    }
+    for rank in 0..8 {
+        let (_, y) = layout.center(('a', rank));
+        let x = MARGIN / 2.0;
+        let y = y + 5.0;
+        let r = rank + 1;
+        writeln!(s, r#"<text x="{x}" y="{y}" {STYLE}>{r}</text>"#).unwrap();
+    }
+}
+
+/// draw the SVG document `svg` scaled into the square whose top left corner
+/// is `(x, y)`
+fn embed_svg(s: &mut String, svg: &[u8], x: f64, y: f64) {
+    writeln!(
+        s,
+        r#"<image x="{x}" y="{y}" width="{SQUARE}" height="{SQUARE}" href="data:image/svg+xml;base64,{}"/>"#,
+        base64(svg)
+    )
+    .unwrap();
+}
+
+/// standard base64 encoding with padding, for embedding data URLs
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// draw an arrow in `brush` from the center of `from` to the center of `to`
+fn write_arrow(
+    s: &mut String,
+    layout: &Layout,
+    from: Coord,
+    to: Coord,
+    brush: Brush,
+) {
+    let (x1, y1) = layout.center(from);
+    let (x2, y2) = layout.center(to);
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let len = dx.hypot(dy);
+    if len == 0.0 {
+        return;
+    }
+    // unit vectors along and perpendicular to the arrow
+    let (ux, uy) = (dx / len, dy / len);
+    let (px, py) = (-uy, ux);
+    let head = 0.4 * SQUARE;
+    let half = 0.25 * SQUARE;
+    // stop the shaft at the base of the head so the two don't overlap
+    let (bx, by) = (x2 - head * ux, y2 - head * uy);
+    let width = 0.18 * SQUARE;
+    let color = brush.hex();
+    writeln!(
+        s,
+        r#"<g fill="{color}" stroke="{color}" opacity="0.8">
+<line x1="{x1}" y1="{y1}" x2="{bx}" y2="{by}" stroke-width="{width}" stroke-linecap="round"/>
+<polygon stroke="none" points="{x2},{y2} {},{} {},{}"/>
+</g>"#,
+        bx + half * px,
+        by + half * py,
+        bx - half * px,
+        by - half * py,
+    )
+    .unwrap();
+}
+
+/// draw `piece` in the square whose top left corner is `(x, y)`
+fn write_piece(s: &mut String, piece: Piece, x: f64, y: f64) {
+    let (fill, detail) = match piece.color {
+        Color::White => ("#ffffff", "#000000"),
+        Color::Black => ("#000000", "#ffffff"),
+    };
+    writeln!(
+        s,
+        r##"<g transform="translate({x},{y})" fill="{fill}" stroke="#000000" stroke-width="1.5" stroke-linejoin="round" stroke-linecap="round">"##
+    )
+    .unwrap();
+    match piece.typ {
+        PieceType::King => {
+            s.push_str(
+                r#"<path d="M 22.5,6 V 14 M 19,9.5 H 26"/>
+<path d="M 11.5,37 H 33.5 L 31.5,29 C 35.5,26 35.5,19 29.5,19 C 26.5,19 24.5,22 22.5,24 C 20.5,22 18.5,19 15.5,19 C 9.5,19 9.5,26 13.5,29 Z"/>
+<circle cx="22.5" cy="18" r="3"/>
+"#,
+            );
+            writeln!(s, r#"<path d="M 13.5,29 H 31.5" stroke="{detail}"/>"#)
+                .unwrap();
+        }
+        PieceType::Queen => {
+            s.push_str(
+                r#"<path d="M 9,26 L 12.5,12 L 17.5,23 L 22.5,10 L 27.5,23 L 32.5,12 L 36,26 C 34,29 33,31 33,33 L 34,37 H 11 L 12,33 C 12,31 11,29 9,26 Z"/>
+<circle cx="12.5" cy="11" r="2.2"/>
+<circle cx="22.5" cy="9" r="2.2"/>
+<circle cx="32.5" cy="11" r="2.2"/>
+"#,
+            );
+            writeln!(s, r#"<path d="M 12,33 H 33" stroke="{detail}"/>"#)
+                .unwrap();
+        }
+        PieceType::Rook => {
+            s.push_str(
+                r#"<path d="M 9,39 H 36 V 36 H 33 V 32 L 30,29 V 17 L 33,14 V 9 H 29 V 12 H 25 V 9 H 20 V 12 H 16 V 9 H 12 V 14 L 15,17 V 29 L 12,32 V 36 H 9 Z"/>
+"#,
+            );
+            writeln!(
+                s,
+                r#"<path d="M 12,36 H 33 M 15,17 H 30 M 15,29 H 30" stroke="{detail}"/>"#
+            )
+            .unwrap();
+        }
+        PieceType::Bishop => {
+            s.push_str(
+                r#"<path d="M 9,39 H 36 V 37 H 9 Z"/>
+<path d="M 15,37 H 30 L 28,33 C 31,30 33,26 33,22 C 33,17 28,13 22.5,10 C 17,13 12,17 12,22 C 12,26 14,30 17,33 Z"/>
+<circle cx="22.5" cy="8" r="2.5"/>
+"#,
+            );
+            writeln!(
+                s,
+                r#"<path d="M 22.5,17 V 25 M 18.5,21 H 26.5 M 17,33 H 28" stroke="{detail}"/>"#
+            )
+            .unwrap();
+        }
+        PieceType::Knight => {
+            s.push_str(
+                r#"<path d="M 12,37 H 34 C 34,28 33,20 29,15 C 27,12 24,10 21,9 L 20,6 L 17,10 C 13,12 9,16 7,21 L 9,24 C 11,24 13,23 15,21 C 16,23 17,24 15,26 C 12,29 11,32 12,37 Z"/>
+"#,
+            );
+            writeln!(
+                s,
+                r#"<circle cx="17" cy="14" r="1" fill="{detail}" stroke="{detail}"/>"#
+            )
+            .unwrap();
+        }
+        PieceType::Pawn => {
+            s.push_str(
+                r#"<path d="M 22.5,9 C 20.29,9 18.5,10.79 18.5,13 C 18.5,13.89 18.79,14.71 19.28,15.38 C 17.33,16.5 16,18.59 16,21 C 16,23.03 16.94,24.84 18.41,26.03 C 15.41,27.09 11,31.58 11,39.5 H 34 C 34,31.58 29.59,27.09 26.59,26.03 C 28.06,24.84 29,23.03 29,21 C 29,18.59 27.67,16.5 25.72,15.38 C 26.21,14.71 26.5,13.89 26.5,13 C 26.5,10.79 24.71,9 22.5,9 Z"/>
+"#,
+            );
+        }
+    }
+    s.push_str("</g>\n");
+}
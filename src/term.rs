@@ -0,0 +1,111 @@
+//! text rendering of a [Board] for display in a terminal
+
+use std::fmt::Write;
+
+use crate::board::{to_idx, Board, Color, Coord, PieceType};
+
+/// square backgrounds as (light, dark) RGB triples
+const SQUARES: [(u8, u8, u8); 2] = [(240, 217, 181), (181, 136, 99)];
+
+/// backgrounds of the squares involved in the last move, as (light, dark)
+const HIGHLIGHTS: [(u8, u8, u8); 2] = [(205, 210, 106), (170, 162, 58)];
+
+impl Board {
+    /// render `self` as text for a terminal, with the squares of `last_move`
+    /// highlighted and the board drawn from Black's perspective if `flip` is
+    /// true. if `ansi` is true, the board is drawn with Unicode pieces on
+    /// 24-bit ANSI colored squares. otherwise it is drawn in plain ASCII with
+    /// FEN piece letters and the last move shown in parentheses
+    pub fn to_term(
+        &self,
+        last_move: Option<(Coord, Coord)>,
+        flip: bool,
+        ansi: bool,
+    ) -> String {
+        let mut ranks: Vec<usize> = (0..8).rev().collect();
+        let mut files: Vec<char> = ('a'..='h').collect();
+        if flip {
+            ranks.reverse();
+            files.reverse();
+        }
+        let moved = |c: Coord| last_move.is_some_and(|(f, t)| c == f || c == t);
+
+        let mut s = String::new();
+        for &rank in &ranks {
+            write!(s, "{} ", rank + 1).unwrap();
+            for &file in &files {
+                let coord = (file, rank);
+                let piece = self[coord];
+                if !ansi {
+                    let c = piece.map_or('.', |p| letter(p.typ, p.color));
+                    if moved(coord) {
+                        write!(s, "({c})").unwrap();
+                    } else {
+                        write!(s, " {c} ").unwrap();
+                    }
+                    continue;
+                }
+                let shade = (to_idx(file) + rank + 1) % 2;
+                let (r, g, b) = if moved(coord) {
+                    HIGHLIGHTS[shade]
+                } else {
+                    SQUARES[shade]
+                };
+                write!(s, "\x1b[48;2;{r};{g};{b}m").unwrap();
+                match piece {
+                    Some(p) => {
+                        let fg = match p.color {
+                            Color::White => 255,
+                            Color::Black => 0,
+                        };
+                        write!(
+                            s,
+                            "\x1b[38;2;{fg};{fg};{fg}m {} ",
+                            glyph(p.typ)
+                        )
+                        .unwrap();
+                    }
+                    None => s.push_str("   "),
+                }
+                s.push_str("\x1b[0m");
+            }
+            s.push('\n');
+        }
+        s.push_str("  ");
+        for file in files {
+            write!(s, " {file} ").unwrap();
+        }
+        s.push('\n');
+        s
+    }
+}
+
+/// the FEN letter for a piece
+fn letter(typ: PieceType, color: Color) -> char {
+    let c = match typ {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn => 'P',
+    };
+    match color {
+        Color::White => c,
+        Color::Black => c.to_ascii_lowercase(),
+    }
+}
+
+/// the solid Unicode chess symbol for a piece. both colors use the solid
+/// glyphs, which are then colored, since the outlined ones are hard to see on
+/// colored squares
+fn glyph(typ: PieceType) -> char {
+    match typ {
+        PieceType::King => '♚',
+        PieceType::Queen => '♛',
+        PieceType::Rook => '♜',
+        PieceType::Bishop => '♝',
+        PieceType::Knight => '♞',
+        PieceType::Pawn => '♟',
+    }
+}
@@ -1,4 +1,27 @@
-use crate::{board::Board, pgn::Pgn};
+use std::{str::FromStr, sync::Arc};
+
+use chrono::Duration;
+
+use crate::{
+    annotation::{self, Brush, Shape},
+    backup,
+    board::{Board, Color, PieceType},
+    cache::RenderCache,
+    clock::{Clock, FixedClock},
+    collection::{Collection, DEFAULT_DECK},
+    config::Config,
+    deck::{
+        self,
+        filter::Filter,
+        simulate::{self, Simulation},
+        Card, CardId, Deck, Limits, Phase, Quality, Review,
+    },
+    optimize,
+    pgn::Pgn,
+    render::{self, RenderError, Renderer, TempDir},
+    scheduler::{Fsrs, Scheduler, SchedulerKind, DEFAULT_WEIGHTS},
+    theme::{piece_file, BoardColors, PieceSet, Rgb, Theme},
+};
 
 #[test]
 fn mov() {
@@ -10,3 +33,1015 @@ fn mov() {
         "8/8/2R2P1p/p3k3/6PP/r7/2pK4/8 w KQkq - 0 1"
     );
 }
+
+#[test]
+fn svg() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut board = Board::new();
+    let moves = board.play(&pgn, 4);
+    let (from, to) = *moves.last().unwrap();
+    let arrow = Shape::Arrow {
+        from,
+        to,
+        brush: Brush::Green,
+    };
+    let svg = board.to_svg(&[arrow], false, &Theme::default());
+    assert!(svg.starts_with("<svg"));
+    // one group per piece plus the arrow
+    assert_eq!(svg.matches("<g ").count(), 33);
+    assert_eq!(svg.matches("<text ").count(), 16);
+}
+
+#[test]
+fn fen_round_trip() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut board = Board::new();
+    board.play(&pgn, 5);
+    let fen = board.to_string();
+    assert_eq!(
+        fen,
+        "rnbqk1nr/pppp1ppp/8/2b1p3/3PP3/5N2/PPP2PPP/RNBQKB1R b KQkq - 0 1"
+    );
+    assert_eq!(Board::from_str(&fen).unwrap().to_string(), fen);
+}
+
+#[test]
+fn legacy_card() {
+    let json = r#"{"cards": [{"pgn": "card.png", "answer": "Nf3",
+        "next_due": "2023-02-10T00:00:00Z", "repetition": 0, "e_factor": 2.5}]}"#;
+    let deck: Deck = serde_json::from_str(json).unwrap();
+    let card = &deck.cards[0];
+    assert!(card.position.is_none());
+    assert_eq!(card.image.as_deref(), Some("card.png"));
+    let json = serde_json::to_string(&deck).unwrap();
+    assert!(json.contains(r#""image":"card.png""#));
+}
+
+#[test]
+fn orientation() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut card = Card::new(&pgn, 5, String::new()).unwrap();
+    let pos = card.position.as_mut().unwrap();
+    let board = pos.board().unwrap();
+    // black to move, so black is at the bottom by default
+    assert!(pos.flip(&board));
+    pos.orientation = Some(Color::White);
+    assert!(!pos.flip(&board));
+    assert_ne!(
+        board.to_svg(&pos.all_shapes(), false, &Theme::default()),
+        board.to_svg(&pos.all_shapes(), true, &Theme::default())
+    );
+}
+
+#[test]
+fn shapes() {
+    let pgn = Pgn::from_str(
+        "[Event \"?\"]
+
+1. e4 e5 2. Nf3 { [%cal Gg1f3,Rd7d5] [%csl Ye5] good } 2... Nc6 *",
+    )
+    .unwrap();
+    assert_eq!(pgn.moves.len(), 2);
+    assert_eq!(pgn.moves[1].black, "Nc6");
+    let want = vec![
+        Shape::Arrow {
+            from: ('g', 0),
+            to: ('f', 2),
+            brush: Brush::Green,
+        },
+        Shape::Arrow {
+            from: ('d', 6),
+            to: ('d', 4),
+            brush: Brush::Red,
+        },
+        Shape::Circle {
+            square: ('e', 4),
+            brush: Brush::Yellow,
+        },
+    ];
+    assert_eq!(annotation::parse_comment(&pgn.comments[&3]), want);
+
+    let card = Card::new(&pgn, 3, String::new()).unwrap();
+    let pos = card.position.unwrap();
+    assert_eq!(pos.shapes, want);
+    let svg = pos.board().unwrap().to_svg(
+        &pos.all_shapes(),
+        false,
+        &Theme::default(),
+    );
+    assert_eq!(svg.matches("<polygon").count(), 3);
+    assert_eq!(svg.matches(r#"fill="none""#).count(), 1);
+}
+
+#[test]
+fn theme() {
+    let board = Board::new();
+    let plain = Theme {
+        board: BoardColors::Blue,
+        coordinates: false,
+        ..Theme::default()
+    };
+    let svg = board.to_svg(&[], false, &plain);
+    assert!(svg.contains("#8ca2ad"));
+    assert!(!svg.contains("<text"));
+    assert!(svg.contains(r#"width="360""#));
+
+    let dir = TempDir::new().unwrap();
+    for c in ['w', 'b'] {
+        for p in ['K', 'Q', 'R', 'B', 'N', 'P'] {
+            std::fs::write(
+                dir.0.join(format!("{c}{p}.svg")),
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="45" height="45"/>"#,
+            )
+            .unwrap();
+        }
+    }
+    let custom = Theme {
+        pieces: PieceSet::directory(&dir.0).unwrap(),
+        border: true,
+        ..Theme::default()
+    };
+    let svg = board.to_svg(&[], false, &custom);
+    assert_eq!(svg.matches("<image").count(), 32);
+    assert!(board.to_png(&[], false, &custom, 64).is_ok());
+
+    let bundled = Theme {
+        pieces: PieceSet::from_name("cburnett").unwrap(),
+        ..Theme::default()
+    };
+    let svg = board.to_svg(&[], false, &bundled);
+    assert_eq!(svg.matches("<image").count(), 32);
+    assert!(board.to_png(&[], false, &bundled, 64).is_ok());
+}
+
+#[test]
+fn term() {
+    let pgn = Pgn::from_str("[Event \"?\"]\n\n1. e4 e5").unwrap();
+    let pos = Card::new(&pgn, 2, String::new()).unwrap().position.unwrap();
+    let plain = pos.to_term(false).unwrap();
+    let lines: Vec<&str> = plain.lines().collect();
+    assert_eq!(lines[0], "8  r  n  b  q  k  b  n  r ");
+    assert_eq!(lines[1], "7  p  p  p  p (.) p  p  p ");
+    assert_eq!(lines[3], "5  .  .  .  . (p) .  .  . ");
+    assert_eq!(lines[4], "4  .  .  .  .  P  .  .  . ");
+    assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+
+    // black to move, so black is at the bottom
+    let pos = Card::new(&pgn, 1, String::new()).unwrap().position.unwrap();
+    let colored = pos.to_term(true).unwrap();
+    assert!(colored.starts_with("1 "));
+    assert!(colored.contains('♚'));
+    assert!(colored.contains("\x1b[0m"));
+}
+
+#[test]
+fn render_errors() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    assert!(matches!(
+        Card::new(&pgn, 0, String::new()),
+        Err(RenderError::Position(_))
+    ));
+    assert!(matches!(
+        Card::new(&pgn, 1000, String::new()),
+        Err(RenderError::Position(_))
+    ));
+
+    let mut card = Card::new(&pgn, 3, String::new()).unwrap();
+    card.position.as_mut().unwrap().fen = "not a fen".to_owned();
+    let theme = Theme::default();
+    assert!(matches!(
+        card.render(Renderer::Native, &theme, 64),
+        Err(RenderError::Position(_))
+    ));
+    card.position = None;
+    assert!(matches!(
+        card.render(Renderer::Native, &theme, 64),
+        Err(RenderError::Empty)
+    ));
+}
+
+#[test]
+fn render_all() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let theme = Theme::default();
+    let cards: Vec<Card> = (1..=12)
+        .map(|n| Card::new(&pgn, n, String::new()).unwrap())
+        .collect();
+    let pngs = render::render_all(&cards, Renderer::Native, &theme, 64);
+    assert_eq!(pngs.len(), cards.len());
+    for (card, png) in cards.iter().zip(pngs) {
+        assert_eq!(
+            png.unwrap(),
+            card.render(Renderer::Native, &theme, 64).unwrap()
+        );
+    }
+    assert!(render::render_all(&[], Renderer::Native, &theme, 64).is_empty());
+}
+
+#[test]
+fn render_cache() {
+    let dir = TempDir::new().unwrap();
+    let cache = RenderCache::new(&dir.0, u64::MAX);
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let card = Card::new(&pgn, 3, String::new()).unwrap();
+    let theme = Theme::default();
+    let png = card
+        .render_cached(&cache, Renderer::Native, &theme, 64)
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 1);
+
+    // a hit doesn't call the renderer
+    let key = "key";
+    cache.get_or_render(key, || Ok(vec![1])).unwrap();
+    let hit = cache
+        .get_or_render(key, || panic!("rendered twice"))
+        .unwrap();
+    assert_eq!(hit, vec![1]);
+    assert_eq!(
+        card.render_cached(&cache, Renderer::Native, &theme, 64)
+            .unwrap(),
+        png
+    );
+
+    // a different style is a different entry
+    let blue = Theme {
+        board: BoardColors::Blue,
+        ..Theme::default()
+    };
+    card.render_cached(&cache, Renderer::Native, &blue, 64)
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 3);
+
+    // editing a piece set's files is a different entry
+    let pieces = TempDir::new().unwrap();
+    for color in [Color::White, Color::Black] {
+        for typ in PieceType::ALL {
+            let svg = PieceSet::Cburnett.bundled(color, typ).unwrap();
+            std::fs::write(pieces.0.join(piece_file(color, typ)), svg).unwrap();
+        }
+    }
+    let custom = Theme {
+        pieces: PieceSet::directory(&pieces.0).unwrap(),
+        ..Theme::default()
+    };
+    let before = card
+        .render_cached(&cache, Renderer::Native, &custom, 64)
+        .unwrap();
+    let white_king = pieces.0.join(piece_file(Color::White, PieceType::King));
+    let black_king = PieceSet::Cburnett.bundled(Color::Black, PieceType::King);
+    std::fs::write(white_king, black_king.unwrap()).unwrap();
+    let after = card
+        .render_cached(&cache, Renderer::Native, &custom, 64)
+        .unwrap();
+    assert_ne!(before, after);
+    assert_eq!(after, card.render(Renderer::Native, &custom, 64).unwrap());
+
+    // only the most recent entry fits
+    let small = RenderCache::new(&dir.0, 10);
+    small.get_or_render("new", || Ok(vec![2; 10])).unwrap();
+    assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 1);
+}
+
+#[test]
+fn line() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let pos = Card::new(&pgn, 4, String::new()).unwrap().position.unwrap();
+    let line = pos.line().unwrap();
+    assert_eq!(line.len(), 5);
+    assert_eq!(line[0].0.to_string(), Board::new().to_string());
+    assert_eq!(line[4].0.to_string(), pos.fen);
+    assert!(line[0].1.is_empty());
+    assert_eq!(line[4].1, pos.all_shapes());
+
+    let theme = Theme::default();
+    assert_eq!(pos.line_pngs(&theme, 32).unwrap().len(), 5);
+    let gif = pos.line_gif(&theme, 32).unwrap();
+    assert!(gif.starts_with(b"GIF89a"));
+
+    let mut legacy = pos.clone();
+    legacy.pgn = None;
+    assert!(matches!(legacy.line(), Err(RenderError::Position(_))));
+}
+
+#[test]
+fn fsrs() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut card = Card::new(&pgn, 3, String::new()).unwrap();
+    let fsrs = Fsrs::default();
+    let now = card.next_due;
+
+    fsrs.update(&mut card, Quality::Four, now);
+    assert_eq!(card.stability, Some(DEFAULT_WEIGHTS[2]));
+    assert_eq!(card.next_due - now, Duration::days(4));
+    let d = card.difficulty.unwrap();
+
+    // a successful review after the interval grows the stability
+    let later = card.next_due;
+    fsrs.update(&mut card, Quality::Four, later);
+    assert!(card.stability.unwrap() > DEFAULT_WEIGHTS[2] * 2.0);
+    assert!(card.next_due - later > Duration::days(8));
+
+    // forgetting shrinks it and makes the card harder
+    let s = card.stability.unwrap();
+    let due = card.next_due;
+    fsrs.update(&mut card, Quality::One, due);
+    assert!(card.stability.unwrap() < s);
+    assert!(card.difficulty.unwrap() > d);
+    assert_eq!(card.repetition, 0);
+
+    // aiming for higher retention means shorter intervals
+    let strict = Fsrs {
+        desired_retention: 0.97,
+        ..Fsrs::default()
+    };
+    assert!(strict.interval(10.0) < fsrs.interval(10.0));
+    assert_eq!(fsrs.interval(10.0), 10.0);
+
+    let mut deck = Deck::default();
+    assert_eq!(deck.scheduler, SchedulerKind::Sm2);
+    deck.scheduler = SchedulerKind::Fsrs(fsrs);
+    let id = deck.push(Card::new(&pgn, 3, String::new()).unwrap());
+    deck.answer(id, Quality::Five, Default::default());
+    assert!(deck.cards[0].stability.is_some());
+    let json = serde_json::to_string(&deck).unwrap();
+    let back: Deck = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.scheduler, deck.scheduler);
+}
+
+#[test]
+fn review_log() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut deck = Deck {
+        learning_steps: Vec::new(),
+        ..Deck::default()
+    };
+    let a = deck.push(Card::new(&pgn, 3, String::new()).unwrap());
+    let b = deck.push(Card::new(&pgn, 4, String::new()).unwrap());
+    deck.answer(a, Quality::Four, std::time::Duration::from_secs(3));
+    deck.answer(b, Quality::Two, std::time::Duration::from_secs(10));
+    deck.answer(a, Quality::Five, std::time::Duration::from_millis(1500));
+
+    assert_eq!(deck.reviews.len(), 3);
+    let first: Vec<_> = deck.reviews_of(a).collect();
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].quality, 4);
+    assert_eq!(first[0].prev_interval, 0.0);
+    assert!((first[0].interval - 1.0).abs() < 1e-3);
+    assert_eq!(first[0].time_taken, 3.0);
+    assert_eq!(first[1].prev_interval, first[0].interval);
+    assert!((first[1].interval - 6.0).abs() < 1e-3);
+    assert_eq!(first[1].ease, deck.cards[0].e_factor);
+
+    let second: Vec<_> = deck.reviews_of(b).collect();
+    assert_eq!(second[0].interval, 0.0);
+
+    let json = serde_json::to_string(&deck).unwrap();
+    let back: Deck = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.reviews, deck.reviews);
+}
+
+#[test]
+fn card_ids() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    let mut deck = Deck::default();
+    let a = deck.push(Card::new(&pgn, 3, "a".to_owned()).unwrap());
+    let b = deck.push(Card::new(&pgn, 4, "b".to_owned()).unwrap());
+    assert_ne!(a, b);
+    assert!(deck.get(b).unwrap().created.is_some());
+    deck.answer(b, Quality::Four, Default::default());
+
+    // editing keeps the schedule of the right card
+    let due = deck.get(b).unwrap().next_due;
+    let edited = Card::new(&pgn, 5, "c".to_owned()).unwrap();
+    assert!(deck.update(b, edited));
+    assert_eq!(deck.get(b).unwrap().answer, "c");
+    assert_eq!(deck.get(b).unwrap().next_due, due);
+    assert_eq!(deck.get(a).unwrap().answer, "a");
+
+    // ids aren't reused after a card is removed
+    assert_eq!(deck.remove(b).unwrap().answer, "c");
+    assert_eq!(deck.reviews_of(b).count(), 1);
+    assert!(deck.get(b).is_none());
+    let c = deck.push(Card::new(&pgn, 5, String::new()).unwrap());
+    assert!(c != a && c != b);
+
+    // cards in older decks are numbered by index, matching their reviews
+    let json = r#"{"cards": [
+        {"answer": "x", "next_due": "2023-02-10T00:00:00Z", "repetition": 0,
+         "e_factor": 2.5},
+        {"answer": "y", "next_due": "2023-02-10T00:00:00Z", "repetition": 0,
+         "e_factor": 2.5}],
+        "reviews": [{"card": 1, "time": "2023-02-09T00:00:00Z", "quality": 4,
+         "prev_interval": 0.0, "interval": 1.0, "ease": 2.5,
+         "time_taken": 1.0}]}"#;
+    let path = std::env::temp_dir()
+        .join(format!("chanki-legacy-{}.json", std::process::id()));
+    std::fs::write(&path, json).unwrap();
+    let mut deck = Deck::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(deck.cards[1].id, CardId(1));
+    assert_eq!(deck.reviews_of(CardId(1)).count(), 1);
+    let id = deck.push(deck.cards[0].clone());
+    assert_eq!(id, CardId(2));
+}
+
+#[test]
+fn optimize() {
+    let fit = |reviews: &[Review], day_start| {
+        let deck = Deck {
+            reviews: reviews.to_vec(),
+            limits: Limits {
+                day_start,
+                ..Limits::default()
+            },
+            ..Deck::default()
+        };
+        optimize::optimize(&Fsrs::default(), &deck)
+    };
+    assert!(fit(&[], 0).is_err());
+
+    // cards that are always forgotten after a few days and remembered the
+    // next day, much worse than the default weights expect
+    let start: chrono::DateTime<chrono::Utc> =
+        "2023-03-01T08:00:00Z".parse().unwrap();
+    let mut reviews = Vec::new();
+    for card in (0..20).map(CardId) {
+        let mut time = start;
+        for i in 0..6 {
+            let (quality, days) = if i % 2 == 1 { (1, 1) } else { (4, 5) };
+            reviews.push(Review {
+                card,
+                time,
+                quality,
+                prev_interval: 0.0,
+                interval: 0.0,
+                ease: 2.5,
+                time_taken: 0.0,
+                new: false,
+                scheduled: true,
+            });
+            time += Duration::days(days);
+        }
+    }
+    let report = fit(&reviews, 0).unwrap();
+    assert_eq!(report.reviews, 100);
+    assert_eq!(report.actual_retention, 0.4);
+    assert!(report.after.log_loss < report.before.log_loss);
+    assert!(
+        (report.after.predicted_retention - 0.4).abs()
+            < (report.before.predicted_retention - 0.4).abs()
+    );
+
+    // answers later the same day don't change the fit, and neither do the
+    // learning steps before a card graduates, even on earlier days
+    let mut with_steps = reviews.clone();
+    for r in &reviews {
+        for (minutes, quality) in [(1, 1), (10, 4)] {
+            with_steps.push(Review {
+                time: r.time + Duration::minutes(minutes),
+                quality,
+                ..r.clone()
+            });
+        }
+    }
+    for card in (0..20).map(CardId) {
+        with_steps.push(Review {
+            card,
+            time: start - Duration::days(1),
+            quality: 1,
+            scheduled: false,
+            ..reviews[0].clone()
+        });
+    }
+    assert_eq!(fit(&with_steps, 0).unwrap(), report);
+
+    // going by the deck's days, which here end at 6:00
+    let mut late = reviews.clone();
+    for r in &reviews {
+        late.push(Review {
+            time: r.time + Duration::hours(20),
+            quality: 1,
+            ..r.clone()
+        });
+    }
+    assert_ne!(fit(&late, 0).unwrap(), report);
+    assert_eq!(fit(&late, 6).unwrap(), report);
+}
+
+#[test]
+fn learning_steps() {
+    assert_eq!(deck::parse_steps("1m, 10m,1d"), Some(vec![1, 10, 1440]));
+    assert_eq!(deck::parse_steps("2h"), Some(vec![120]));
+    assert_eq!(deck::parse_steps(""), Some(vec![]));
+    assert_eq!(deck::parse_steps("10"), None);
+    assert_eq!(deck::parse_steps("0m"), None);
+    assert_eq!(deck::format_steps(&[1, 10, 1440, 120]), "1m,10m,1d,2h");
+
+    let pgn = Pgn::load("test.pgn").unwrap();
+    // a fixed time keeps the repetitions below on the same day
+    let start = "2023-03-01T08:00:00Z".parse().unwrap();
+    let clock = FixedClock::new(start);
+    let mut deck = Deck {
+        clock: Arc::new(clock.clone()),
+        ..Deck::default()
+    };
+    let card =
+        Card::new_at(&pgn, 3, String::new(), start - Duration::seconds(1));
+    let id = deck.push(card.unwrap());
+    let answer = |deck: &mut Deck, q, minutes| {
+        clock.set(start + Duration::minutes(minutes));
+        deck.answer(id, q, Default::default());
+        (deck.cards[0].phase, deck.cards[0].next_due - clock.now())
+    };
+    let queue = |deck: &Deck, minutes| {
+        clock.set(start + Duration::minutes(minutes));
+        deck.queue()
+    };
+    assert_eq!(queue(&deck, 0), vec![id]);
+
+    // good moves through the steps, again starts them over, and hard
+    // repeats the current one
+    assert_eq!(
+        answer(&mut deck, Quality::Four, 0),
+        (Phase::Learning(1), Duration::minutes(10))
+    );
+    assert_eq!(
+        answer(&mut deck, Quality::Three, 10),
+        (Phase::Learning(1), Duration::minutes(10))
+    );
+    assert_eq!(
+        answer(&mut deck, Quality::One, 20),
+        (Phase::Learning(0), Duration::minutes(1))
+    );
+    assert_eq!(deck.cards[0].repetition, 0);
+
+    // a card in learning is shown early when nothing else is due
+    assert_eq!(queue(&deck, 20), vec![id]);
+
+    answer(&mut deck, Quality::Four, 21);
+    answer(&mut deck, Quality::Four, 31);
+    assert_eq!(
+        answer(&mut deck, Quality::Four, 31 + 1440),
+        (Phase::Review, Duration::days(1))
+    );
+    assert_eq!(deck.cards[0].repetition, 1);
+
+    // a lapse goes through relearning before returning to review
+    let day = 31 + 1440 + 1440;
+    assert_eq!(
+        answer(&mut deck, Quality::Two, day),
+        (Phase::Relearning(0), Duration::minutes(10))
+    );
+    assert_eq!(
+        answer(&mut deck, Quality::Four, day + 10),
+        (Phase::Review, Duration::days(1))
+    );
+
+    // a 3 in review keeps its new interval but is repeated today until it
+    // scores at least 4, without changing the schedule
+    let day = day + 10 + 1440;
+    let (_, interval) = answer(&mut deck, Quality::Three, day);
+    assert_eq!(interval, Duration::days(6));
+    let due = deck.cards[0].next_due;
+    assert_eq!(queue(&deck, day + 1), vec![id]);
+    answer(&mut deck, Quality::Three, day + 1);
+    answer(&mut deck, Quality::Four, day + 2);
+    assert_eq!(deck.cards[0].next_due, due);
+    assert!(queue(&deck, day + 3).is_empty());
+
+    // only graduating, lapsing, and reviews reach the scheduler
+    let scheduled: Vec<_> = deck.reviews.iter().map(|r| r.scheduled).collect();
+    let (f, t) = (false, true);
+    assert_eq!(scheduled, [f, f, f, f, f, t, t, t, t, f, f]);
+}
+
+/// a deck with one card and no learning steps, so that answers go straight
+/// to the scheduler, on a clock that starts when the card is due
+fn sm2_deck() -> (Deck, FixedClock) {
+    let start = "2023-03-01T08:00:00Z".parse().unwrap();
+    let clock = FixedClock::new(start);
+    let mut deck = Deck {
+        learning_steps: Vec::new(),
+        relearning_steps: Vec::new(),
+        clock: Arc::new(clock.clone()),
+        ..Deck::default()
+    };
+    let pgn = Pgn::load("test.pgn").unwrap();
+    deck.push(Card::new_at(&pgn, 3, String::new(), start).unwrap());
+    clock.advance(Duration::seconds(1));
+    (deck, clock)
+}
+
+/// answer the card in `deck` with each quality in turn, moving `clock` to
+/// when it is next due before each answer. returns the intervals in days
+fn sm2_intervals(deck: &mut Deck, clock: &FixedClock, qs: &[u8]) -> Vec<i64> {
+    qs.iter()
+        .map(|&q| {
+            clock.set(deck.cards[0].next_due + Duration::seconds(1));
+            let q = Quality::try_from(q).unwrap();
+            deck.answer(deck.cards[0].id, q, Default::default());
+            (deck.cards[0].next_due - clock.now()).num_days()
+        })
+        .collect()
+}
+
+#[test]
+fn sm2_intervals_grow_by_ease() {
+    let (mut deck, clock) = sm2_deck();
+    // with 4s the ease stays at 2.5: 1, 6, then each interval times 2.5
+    // rounded up
+    let intervals = sm2_intervals(&mut deck, &clock, &[4, 4, 4, 4, 4]);
+    assert_eq!(intervals, vec![1, 6, 15, 38, 95]);
+    assert_eq!(deck.cards[0].e_factor, 2.5);
+    assert_eq!(deck.cards[0].repetition, 5);
+}
+
+#[test]
+fn sm2_ease() {
+    let (mut deck, clock) = sm2_deck();
+    sm2_intervals(&mut deck, &clock, &[5]);
+    assert!((deck.cards[0].e_factor - 2.6).abs() < 1e-9);
+    sm2_intervals(&mut deck, &clock, &[3]);
+    assert!((deck.cards[0].e_factor - 2.46).abs() < 1e-9);
+
+    // failing resets the repetitions but not the ease
+    let intervals = sm2_intervals(&mut deck, &clock, &[1]);
+    assert_eq!(intervals, vec![0]);
+    assert_eq!(deck.cards[0].repetition, 0);
+    assert!((deck.cards[0].e_factor - 2.46).abs() < 1e-9);
+    assert_eq!(sm2_intervals(&mut deck, &clock, &[4, 4]), vec![1, 6]);
+
+    // the ease never drops below 1.3
+    sm2_intervals(&mut deck, &clock, &[3; 10]);
+    assert_eq!(deck.cards[0].e_factor, 1.3);
+}
+
+#[test]
+fn sm2_due() {
+    let (mut deck, clock) = sm2_deck();
+    let id = deck.cards[0].id;
+    assert_eq!(deck.queue(), vec![id]);
+    deck.answer(id, Quality::Four, Default::default());
+    assert!(deck.queue().is_empty());
+    clock.advance(Duration::hours(23));
+    assert!(deck.queue().is_empty());
+    clock.advance(Duration::hours(2));
+    assert_eq!(deck.queue(), vec![id]);
+}
+
+#[test]
+fn suspend_and_bury() {
+    let (mut deck, clock) = sm2_deck();
+    let id = deck.cards[0].id;
+    assert!(deck.suspend(id, true));
+    assert!(!deck.is_due(&deck.cards[0]));
+    assert!(deck.queue().is_empty());
+    deck.suspend(id, false);
+    assert_eq!(deck.queue(), vec![id]);
+
+    // a buried card comes back the next day
+    assert!(deck.bury(id, true));
+    assert!(deck.queue().is_empty());
+    clock.advance(Duration::days(1));
+    assert_eq!(deck.queue(), vec![id]);
+    deck.bury(id, true);
+    deck.bury(id, false);
+    assert_eq!(deck.queue(), vec![id]);
+
+    assert!(deck.remove(id).is_some());
+    assert!(!deck.suspend(id, true));
+    assert!(!deck.bury(id, true));
+}
+
+#[test]
+fn daily_limits() {
+    let (mut deck, clock) = sm2_deck();
+    let pgn = Pgn::load("test.pgn").unwrap();
+    for answer in ["b", "c"] {
+        deck.push(
+            Card::new_at(&pgn, 3, answer.to_owned(), clock.now()).unwrap(),
+        );
+    }
+    clock.advance(Duration::seconds(1));
+    deck.limits = Limits {
+        new_per_day: 2,
+        reviews_per_day: 0,
+        day_start: 9,
+    };
+    let ids: Vec<_> = deck.cards.iter().map(|c| c.id).collect();
+    assert_eq!(deck.queue(), ids[..2]);
+
+    // cards seen today count towards the limit
+    deck.answer(ids[0], Quality::Four, Default::default());
+    assert_eq!(deck.studied_today(), (1, 0));
+    assert_eq!(deck.queue(), ids[1..2]);
+
+    // it is 8:00, so the next day starts in an hour
+    clock.advance(Duration::minutes(59));
+    assert_eq!(deck.queue(), ids[1..2]);
+    clock.advance(Duration::minutes(1));
+    assert_eq!(deck.studied_today(), (0, 0));
+    assert_eq!(deck.queue(), ids[1..]);
+
+    // the reviewed card is due again, but there are no reviews to spare
+    clock.advance(Duration::days(1));
+    assert_eq!(deck.queue(), ids[1..]);
+    deck.limits.reviews_per_day = 1;
+    assert_eq!(deck.queue(), [ids[1], ids[2], ids[0]]);
+
+    // forecasts ignore the limits
+    let sim = Simulation {
+        days: 1,
+        ..Simulation::default()
+    };
+    assert_eq!(simulate::forecast(&deck, &sim)[0].cards, 3);
+}
+
+#[test]
+fn legacy_reviews_not_new() {
+    let (mut deck, clock) = sm2_deck();
+    let pgn = Pgn::load("test.pgn").unwrap();
+    for _ in 0..3 {
+        deck.push(Card::new(&pgn, 3, String::new()).unwrap());
+    }
+    // review cards from before the review log was kept have no entries in it
+    for card in &mut deck.cards {
+        card.phase = Phase::Review;
+        card.repetition = 2;
+        card.next_due = clock.now() - Duration::days(1);
+    }
+    deck.limits.new_per_day = 1;
+    assert_eq!(deck.queue().len(), 4);
+    deck.answer(deck.cards[0].id, Quality::Four, Default::default());
+    assert_eq!(deck.studied_today(), (0, 1));
+    assert_eq!(deck.queue().len(), 3);
+}
+
+#[test]
+fn leeches() {
+    let (mut deck, clock) = sm2_deck();
+    deck.leech_threshold = 4;
+    // failing while still learning isn't a lapse
+    sm2_intervals(&mut deck, &clock, &[1, 4, 1, 4, 1, 4, 1]);
+    assert_eq!(deck.cards[0].lapses, 3);
+    assert!(deck.leeches().is_empty());
+
+    sm2_intervals(&mut deck, &clock, &[4, 1]);
+    assert_eq!(deck.cards[0].lapses, 4);
+    assert!(deck.cards[0].is_leech());
+    assert!(!deck.cards[0].suspended);
+    assert_eq!(deck.leeches().len(), 1);
+
+    // suspended again every half threshold after that
+    deck.leech_suspend = true;
+    sm2_intervals(&mut deck, &clock, &[4, 1]);
+    assert!(!deck.cards[0].suspended);
+    sm2_intervals(&mut deck, &clock, &[4, 1]);
+    assert!(deck.cards[0].suspended);
+    let tags = &deck.cards[0].tags;
+    assert_eq!(tags.iter().filter(|t| *t == deck::LEECH).count(), 1);
+}
+
+#[test]
+fn one_lapse_per_day() {
+    let (mut deck, clock) = sm2_deck();
+    // without relearning steps, a failed card is due again right away
+    sm2_intervals(&mut deck, &clock, &[4, 1, 1, 1]);
+    assert_eq!(deck.cards[0].lapses, 1);
+    assert_eq!(deck.reviews.len(), 4);
+
+    // but failing it again on a later day is another lapse
+    sm2_intervals(&mut deck, &clock, &[4, 1]);
+    assert_eq!(deck.cards[0].lapses, 2);
+}
+
+#[test]
+fn tags() {
+    let pgn = Pgn::load("test.pgn").unwrap();
+    assert_eq!(pgn.tag("ECO"), Some("C21"));
+    assert_eq!(pgn.tag("White"), Some("gmtrejo"));
+    let card = Card::new(&pgn, 3, String::new()).unwrap();
+    assert_eq!(card.tags, vec!["ECO:C21", "color:black"]);
+
+    let pgn = Pgn::from_str(
+        "[Opening \"King's Pawn Game\"]\n\n1. e4 e5 2. Nf3 Nc6 *",
+    )
+    .unwrap();
+    let mut card = Card::new(&pgn, 3, String::new()).unwrap();
+    assert_eq!(card.tags, vec!["Opening:King's_Pawn_Game", "color:black"]);
+    card.add_tag("endgame");
+    card.add_tag("endgame");
+    assert_eq!(card.tags.len(), 3);
+
+    let matches =
+        |expr: &str, card: &Card| expr.parse::<Filter>().unwrap().matches(card);
+    assert!(matches("opening:king*", &card));
+    assert!(!matches("opening:king* and not endgame", &card));
+    assert!(!matches("ECO:C2* and not endgame", &card));
+    assert!(matches(
+        "ECO:C2* or (color:black and not color:white)",
+        &card
+    ));
+    assert!(matches("not (ECO:* or color:white)", &card));
+    for bad in ["", "and", "ECO:C2* and", "(endgame", "endgame)", "a b"] {
+        assert!(bad.parse::<Filter>().is_err(), "{bad}");
+    }
+
+    let mut deck = Deck::default();
+    let a = deck.push(card);
+    deck.push(
+        Card::new(&Pgn::load("test.pgn").unwrap(), 3, String::new()).unwrap(),
+    );
+    let filter = "ECO:C2* and not endgame".parse().unwrap();
+    assert_eq!(deck.queue().len(), 2);
+    assert!(!deck.queue_matching(&filter).contains(&a));
+    assert_eq!(deck.queue_matching(&filter).len(), 1);
+}
+
+#[test]
+fn collection() {
+    let dir = std::env::temp_dir()
+        .join(format!("chanki-test-collection-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let decks = Collection::open(&dir).unwrap();
+    assert_eq!(decks.names().unwrap(), vec![DEFAULT_DECK]);
+    assert_eq!(decks.current().unwrap(), DEFAULT_DECK);
+
+    // sub-decks create their parents
+    decks.create("white/sicilian").unwrap();
+    decks.create("white-gambits").unwrap();
+    assert!(decks.create("white").is_err());
+    for bad in ["", "a//b", "../x", "a/.b", " "] {
+        assert!(decks.create(bad).is_err(), "{bad:?}");
+    }
+    assert_eq!(
+        decks.names().unwrap(),
+        vec!["default", "white", "white/sicilian", "white-gambits"]
+    );
+
+    // each deck keeps its own settings
+    let mut deck = decks.load("white/sicilian").unwrap();
+    deck.leech_threshold = 3;
+    decks.save("white/sicilian", &deck).unwrap();
+    assert_eq!(decks.load("white").unwrap().leech_threshold, 8);
+
+    decks.switch("white/sicilian").unwrap();
+    decks.rename("white", "repertoire/white").unwrap();
+    assert_eq!(decks.current().unwrap(), "repertoire/white/sicilian");
+    let sicilian = decks.load("repertoire/white/sicilian").unwrap();
+    assert_eq!(sicilian.leech_threshold, 3);
+    assert!(decks.rename("repertoire", "repertoire/x").is_err());
+    assert!(decks.rename("nothing", "x").is_err());
+    assert!(decks.rename("default", "white-gambits").is_err());
+
+    decks.delete("repertoire").unwrap();
+    assert_eq!(decks.names().unwrap(), vec!["default", "white-gambits"]);
+    assert_eq!(decks.current().unwrap(), "default");
+    assert!(decks.switch("repertoire").is_err());
+
+    // a deck named like the current deck's file can still have sub-decks
+    decks.create("current/x").unwrap();
+    decks.switch("current/x").unwrap();
+    assert_eq!(decks.current().unwrap(), "current/x");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backups() {
+    let dir = std::env::temp_dir()
+        .join(format!("chanki-test-backups-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let decks = Collection::open(&dir).unwrap();
+    let path = decks.path(DEFAULT_DECK);
+    let (mut deck, clock) = sm2_deck();
+
+    // saving backs up the old deck at most once an hour
+    for minutes in [0, 30, 60] {
+        clock.set("2023-03-01T08:00:00Z".parse().unwrap());
+        clock.advance(Duration::minutes(minutes));
+        deck.dump(&path).unwrap();
+    }
+    let backups = backup::list(&path).unwrap();
+    assert_eq!(backups.len(), 2);
+    assert!(backups[0].time > backups[1].time);
+    assert_eq!(Deck::load(&backups[0].path).unwrap().cards.len(), 1);
+
+    for _ in 0..backup::KEPT {
+        clock.advance(Duration::hours(1));
+        deck.dump(&path).unwrap();
+    }
+    assert_eq!(backup::list(&path).unwrap().len(), backup::KEPT);
+    let leftovers: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|f| f.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+    assert_eq!(decks.names().unwrap(), vec![DEFAULT_DECK]);
+
+    // restoring backs up the deck it replaces
+    let oldest = backup::list(&path).unwrap().pop().unwrap();
+    deck.cards.clear();
+    deck.dump(&path).unwrap();
+    clock.advance(Duration::minutes(1));
+    backup::restore(&path, &oldest, clock.now()).unwrap();
+    assert_eq!(Deck::load(&path).unwrap().cards.len(), 1);
+    let newest = &backup::list(&path).unwrap()[0];
+    assert_eq!(newest.time, clock.now());
+    assert!(Deck::load(&newest.path).unwrap().cards.is_empty());
+
+    // backups follow their deck when it is renamed, and go when it is
+    // deleted
+    let count = backup::list(&path).unwrap().len();
+    decks.rename(DEFAULT_DECK, "old/renamed").unwrap();
+    let renamed = decks.path("old/renamed");
+    assert_eq!(backup::list(&renamed).unwrap().len(), count);
+    assert!(backup::list(&path).unwrap().is_empty());
+    decks.create(DEFAULT_DECK).unwrap();
+    decks.delete("old").unwrap();
+    decks.rename(DEFAULT_DECK, "old/renamed").unwrap();
+    assert!(backup::list(&renamed).unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn config() {
+    let config: Config = toml::from_str(
+        r#"
+        renderer = "Latex"
+
+        [theme]
+        board = "Blue"
+
+        [new_deck_limits]
+        new_per_day = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.renderer, Renderer::Latex);
+    assert_eq!(config.theme.board, BoardColors::Blue);
+    assert!(config.theme.coordinates);
+    assert_eq!(config.new_deck_limits.new_per_day, 5);
+    assert_eq!(config.new_deck_limits.reviews_per_day, 200);
+    assert_eq!(config.new_deck().limits.new_per_day, 5);
+    assert_eq!(config.decks_dir(), Collection::default_dir());
+
+    let path = std::env::temp_dir()
+        .join(format!("chanki-test-config-{}", std::process::id()))
+        .join("config.toml");
+    assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+    config.save_to(&path).unwrap();
+    assert_eq!(Config::load_from(&path).unwrap(), config);
+    std::fs::write(&path, "[limits]\nnew_per_day = 7").unwrap();
+    let old = Config::load_from(&path).unwrap();
+    assert_eq!(old.new_deck_limits.new_per_day, 7);
+    std::fs::write(&path, "limits = 3").unwrap();
+    assert!(Config::load_from(&path).is_err());
+
+    let custom = |light: &str| {
+        toml::from_str::<Config>(&format!(
+            "[theme.board.Custom]\nlight = {light:?}\ndark = \"#B58863\""
+        ))
+        .map(|c| c.theme.board.colors())
+    };
+    let (light, dark) = custom("white").unwrap();
+    assert_eq!((light, dark), (Rgb(255, 255, 255), Rgb(0xb5, 0x88, 0x63)));
+    assert_eq!(dark.to_string(), "#b58863");
+    for bad in ["#f0d9b", "f0d9b5", "#f0d9bg", "#fff\" onload=\"x"] {
+        assert!(custom(bad).is_err(), "{bad}");
+    }
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn forecast() {
+    let (deck, _) = sm2_deck();
+    let sim = Simulation {
+        days: 10,
+        retention: 1.0,
+        ..Simulation::default()
+    };
+    // no learning steps, so the one card is due on days 0, 1, and 7
+    let days = simulate::forecast(&deck, &sim);
+    let cards: Vec<_> = days.iter().map(|d| d.cards).collect();
+    assert_eq!(cards, vec![1, 1, 0, 0, 0, 0, 0, 1, 0, 0]);
+    assert_eq!(deck.cards[0].repetition, 0);
+    assert!(deck.reviews.is_empty());
+
+    let sim = Simulation {
+        days: 5,
+        retention: 0.8,
+        new_cards: 30,
+        new_per_day: 10,
+        seed: 1,
+    };
+    let days = simulate::forecast(&Deck::default(), &sim);
+    let new: Vec<_> = days.iter().map(|d| d.new).collect();
+    assert_eq!(new, vec![10, 10, 10, 0, 0]);
+    // each new card goes through at least the 1m and 10m steps on its first
+    // day
+    assert!(days[0].reviews >= 20);
+    assert_eq!(days[0].cards, 10);
+    assert!(days[3].cards > 0);
+    assert_eq!(days, simulate::forecast(&Deck::default(), &sim));
+}
@@ -0,0 +1,304 @@
+//! the appearance of rendered diagrams: square colors, piece set, and
+//! decorations around the board
+
+use std::{error::Error, fmt::Display, fs, io, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{Color, PieceType},
+    cache::fnv1a,
+};
+
+/// a color written as `#rrggbb`, which can also be parsed from one of the
+/// [Rgb::NAMED] colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// the basic HTML color names
+    pub const NAMED: [(&'static str, Rgb); 16] = [
+        ("black", Rgb(0x00, 0x00, 0x00)),
+        ("silver", Rgb(0xc0, 0xc0, 0xc0)),
+        ("gray", Rgb(0x80, 0x80, 0x80)),
+        ("white", Rgb(0xff, 0xff, 0xff)),
+        ("maroon", Rgb(0x80, 0x00, 0x00)),
+        ("red", Rgb(0xff, 0x00, 0x00)),
+        ("purple", Rgb(0x80, 0x00, 0x80)),
+        ("fuchsia", Rgb(0xff, 0x00, 0xff)),
+        ("green", Rgb(0x00, 0x80, 0x00)),
+        ("lime", Rgb(0x00, 0xff, 0x00)),
+        ("olive", Rgb(0x80, 0x80, 0x00)),
+        ("yellow", Rgb(0xff, 0xff, 0x00)),
+        ("navy", Rgb(0x00, 0x00, 0x80)),
+        ("blue", Rgb(0x00, 0x00, 0xff)),
+        ("teal", Rgb(0x00, 0x80, 0x80)),
+        ("aqua", Rgb(0x00, 0xff, 0xff)),
+    ];
+}
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// a string that is neither `#rrggbb` nor one of the [Rgb::NAMED] colors
+#[derive(Debug)]
+pub struct ColorError(pub String);
+
+impl Display for ColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid color {:?}, expected #rrggbb or a name", self.0)
+    }
+}
+
+impl Error for ColorError {}
+
+impl FromStr for Rgb {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, rgb)) = Self::NAMED
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(*rgb);
+        }
+        let err = || ColorError(s.to_owned());
+        let hex = s.strip_prefix('#').ok_or_else(err)?;
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+        let channel = |i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        Ok(Rgb(channel(0), channel(2), channel(4)))
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = ColorError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(rgb: Rgb) -> Self {
+        rgb.to_string()
+    }
+}
+
+/// color schemes for the squares of the board, named after their Lichess
+/// counterparts
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum BoardColors {
+    #[default]
+    Brown,
+    Blue,
+    Green,
+    Grey,
+
+    /// arbitrary `light` and `dark` colors, given as strings like `#f0d9b5`
+    /// or `white`
+    Custom {
+        light: Rgb,
+        dark: Rgb,
+    },
+}
+
+impl BoardColors {
+    /// the named schemes, for listing in the user interfaces
+    pub const NAMED: [BoardColors; 4] = [
+        BoardColors::Brown,
+        BoardColors::Blue,
+        BoardColors::Green,
+        BoardColors::Grey,
+    ];
+
+    /// the (light, dark) square colors for `self`
+    pub fn colors(&self) -> (Rgb, Rgb) {
+        match *self {
+            BoardColors::Brown => {
+                (Rgb(0xf0, 0xd9, 0xb5), Rgb(0xb5, 0x88, 0x63))
+            }
+            BoardColors::Blue => (Rgb(0xde, 0xe3, 0xe6), Rgb(0x8c, 0xa2, 0xad)),
+            BoardColors::Green => {
+                (Rgb(0xff, 0xff, 0xdd), Rgb(0x86, 0xa6, 0x66))
+            }
+            BoardColors::Grey => (Rgb(0xdc, 0xdc, 0xdc), Rgb(0xa9, 0xa9, 0xa9)),
+            BoardColors::Custom { light, dark } => (light, dark),
+        }
+    }
+
+    /// look up one of the [Self::NAMED] schemes by its lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED
+            .into_iter()
+            .find(|b| format!("{b:?}").eq_ignore_ascii_case(name))
+    }
+}
+
+/// the SVG of one piece of a set bundled in `pieces/$set`, which has the same
+/// layout as a [PieceSet::Directory]
+macro_rules! bundled {
+    ($set:literal, $color:expr, $typ:expr) => {
+        match ($color, $typ) {
+            (Color::White, PieceType::King) => {
+                include_str!(concat!("../pieces/", $set, "/wK.svg"))
+            }
+            (Color::White, PieceType::Queen) => {
+                include_str!(concat!("../pieces/", $set, "/wQ.svg"))
+            }
+            (Color::White, PieceType::Rook) => {
+                include_str!(concat!("../pieces/", $set, "/wR.svg"))
+            }
+            (Color::White, PieceType::Bishop) => {
+                include_str!(concat!("../pieces/", $set, "/wB.svg"))
+            }
+            (Color::White, PieceType::Knight) => {
+                include_str!(concat!("../pieces/", $set, "/wN.svg"))
+            }
+            (Color::White, PieceType::Pawn) => {
+                include_str!(concat!("../pieces/", $set, "/wP.svg"))
+            }
+            (Color::Black, PieceType::King) => {
+                include_str!(concat!("../pieces/", $set, "/bK.svg"))
+            }
+            (Color::Black, PieceType::Queen) => {
+                include_str!(concat!("../pieces/", $set, "/bQ.svg"))
+            }
+            (Color::Black, PieceType::Rook) => {
+                include_str!(concat!("../pieces/", $set, "/bR.svg"))
+            }
+            (Color::Black, PieceType::Bishop) => {
+                include_str!(concat!("../pieces/", $set, "/bB.svg"))
+            }
+            (Color::Black, PieceType::Knight) => {
+                include_str!(concat!("../pieces/", $set, "/bN.svg"))
+            }
+            (Color::Black, PieceType::Pawn) => {
+                include_str!(concat!("../pieces/", $set, "/bP.svg"))
+            }
+        }
+    };
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PieceSet {
+    /// the simple piece drawings built into chanki
+    #[default]
+    Builtin,
+
+    /// Colin M.L. Burnett's pieces, the Lichess default, bundled with chanki.
+    /// see `pieces/cburnett/LICENSE`
+    Cburnett,
+
+    /// a directory of SVG files named like `wK.svg` and `bN.svg`, the layout
+    /// used by Lichess for sets like cburnett and merida. each file should
+    /// have a square viewBox
+    Directory(PathBuf),
+}
+
+impl PieceSet {
+    /// the sets that don't need a directory, for listing in the user
+    /// interfaces
+    pub const NAMED: [PieceSet; 2] = [PieceSet::Builtin, PieceSet::Cburnett];
+
+    /// look up one of the [Self::NAMED] sets by its lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMED
+            .into_iter()
+            .find(|p| format!("{p:?}").eq_ignore_ascii_case(name))
+    }
+
+    /// the SVG bundled for a piece, or `None` if `self` isn't a bundled set
+    pub(crate) fn bundled(
+        &self,
+        color: Color,
+        typ: PieceType,
+    ) -> Option<&'static str> {
+        match self {
+            PieceSet::Cburnett => Some(bundled!("cburnett", color, typ)),
+            PieceSet::Builtin | PieceSet::Directory(_) => None,
+        }
+    }
+
+    /// construct a [PieceSet::Directory] from `dir`, checking that it
+    /// contains all twelve pieces
+    pub fn directory(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        for color in [Color::White, Color::Black] {
+            for typ in PieceType::ALL {
+                let path = dir.join(piece_file(color, typ));
+                if !path.is_file() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("missing piece {}", path.display()),
+                    ));
+                }
+            }
+        }
+        Ok(PieceSet::Directory(dir))
+    }
+
+    /// a hash of the files of a [Self::Directory], which changes when they
+    /// are edited or replaced. `None` for the sets built into chanki
+    pub(crate) fn files_hash(&self) -> Option<u64> {
+        let PieceSet::Directory(dir) = self else {
+            return None;
+        };
+        let mut bytes = Vec::new();
+        for color in [Color::White, Color::Black] {
+            for typ in PieceType::ALL {
+                // a missing file hashes like an empty one, and fails to render
+                let svg = fs::read(dir.join(piece_file(color, typ)));
+                bytes.extend(svg.unwrap_or_default());
+                bytes.push(0);
+            }
+        }
+        Some(fnv1a(&bytes))
+    }
+}
+
+/// the file name for a piece in a [PieceSet::Directory]
+pub(crate) fn piece_file(color: Color, typ: PieceType) -> String {
+    let c = match color {
+        Color::White => 'w',
+        Color::Black => 'b',
+    };
+    let t = match typ {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn => 'P',
+    };
+    format!("{c}{t}.svg")
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub board: BoardColors,
+    pub pieces: PieceSet,
+
+    /// whether to label the ranks and files
+    pub coordinates: bool,
+
+    /// whether to draw a frame around the board
+    pub border: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            board: BoardColors::default(),
+            pieces: PieceSet::default(),
+            coordinates: true,
+            border: false,
+        }
+    }
+}